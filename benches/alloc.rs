@@ -0,0 +1,67 @@
+//! Counts heap allocations and time per print, comparing the macros with the
+//! dynamic `Printer` API.
+//!
+//! Run with `cargo bench --bench alloc > /dev/null`; results go to stderr.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use pyprint::{dprint, pprint, Printer};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ITERS: usize = 100_000;
+
+fn measure(name: &str, mut f: impl FnMut(usize)) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..ITERS {
+        f(i);
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    eprintln!(
+        "{name:<24} {:>6.2} allocations/print {:>10.1?}/print",
+        allocations as f64 / ITERS as f64,
+        elapsed / ITERS as u32
+    );
+}
+
+fn main() {
+    // Initializes the stdout buffer, which is allocated once per process.
+    pprint!("warm up").unwrap();
+
+    measure("pprint!", |i| pprint!(i, i * 2, i * 3, sep=",").unwrap());
+    measure("dprint!", |i| dprint!("item", Some(i), sep=",").unwrap());
+    measure("Printer::add_element", |i| {
+        Printer::new()
+            .add_element(i.to_string())
+            .add_element((i * 2).to_string())
+            .add_element((i * 3).to_string())
+            .set_sep(",")
+            .print()
+            .unwrap()
+    });
+}
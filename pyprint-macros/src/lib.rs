@@ -0,0 +1,470 @@
+//! Procedural macros for [`pyprint`](https://docs.rs/pyprint).
+//!
+//! These macros are implementation details; use them through the macros
+//! exported by `pyprint`, such as `fprint!`.
+
+use proc_macro::{Delimiter, Group, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// A piece of an f-string: literal text or a replacement field.
+#[derive(Debug, PartialEq)]
+enum Piece<'s> {
+    Text(String),
+    Field(Field<'s>),
+}
+
+/// A replacement field: `{expr[=][!conversion][:spec]}`.
+#[derive(Debug, PartialEq)]
+struct Field<'s> {
+    /// The Rust expression whose value is printed.
+    expr: &'s str,
+    /// The verbatim text printed before the value of a self-documenting field (`{x=}`).
+    label: Option<&'s str>,
+    /// `r` for Debug or `s` for Display.
+    conversion: Option<char>,
+    /// A Python format spec, possibly with nested fields.
+    spec: Option<&'s str>,
+}
+
+/// Splits an f-string into text and replacement fields.
+fn parse_fstring(s: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while let Some(c) = s[i..].chars().next() {
+        let next = s[i + c.len_utf8()..].chars().next();
+        match (c, next) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                text.push(c);
+                i += 2;
+            }
+            ('{', _) => {
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                let (field, end) = parse_field(s, i + 1)?;
+                pieces.push(Piece::Field(field));
+                i = end + 1;
+            }
+            ('}', _) => return Err(String::from("f-string: single '}' is not allowed")),
+            _ => {
+                text.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// Parses the field starting at byte `start`, just after its `{`.
+///
+/// Returns the field and the index of its closing `}`.
+fn parse_field(s: &str, start: usize) -> Result<(Field<'_>, usize), String> {
+    let bytes = s.as_bytes();
+    let at = |i: usize| bytes.get(i).copied();
+    let mut depth = 0usize;
+    let mut i = start;
+    // Find the end of the expression: a top-level `}`, `!`, `:` or self-documenting `=`.
+    // String and char literals are skipped, as their contents may contain any of these.
+    let expr_end = loop {
+        let Some(b) = at(i) else {
+            return Err(String::from("f-string: expecting '}'"));
+        };
+        match b {
+            b'(' | b'[' | b'{' => depth += 1,
+            b'}' if depth == 0 => break i,
+            b')' | b']' | b'}' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("f-string: unmatched '{}'", b as char))?;
+            }
+            b'"' => {
+                i += 1;
+                while at(i).is_some_and(|b| b != b'"') {
+                    i += if at(i) == Some(b'\\') { 2 } else { 1 };
+                }
+            }
+            b'\'' => {
+                // A char literal such as `'}'` or `'\''`; a lifetime or label is left alone
+                if at(i + 1) == Some(b'\\') {
+                    i += 3;
+                    while at(i).is_some_and(|b| b != b'\'') {
+                        i += 1;
+                    }
+                } else if let Some(c) = s[i + 1..].chars().next() {
+                    if at(i + 1 + c.len_utf8()) == Some(b'\'') {
+                        i += 1 + c.len_utf8();
+                    }
+                }
+            }
+            // `!` starts a conversion unless it is `!=` or the `!` of a macro call
+            b'!' if depth == 0
+                && at(i + 1) != Some(b'=')
+                && !s[i + 1..].trim_start().starts_with(['(', '[', '{']) =>
+            {
+                break i
+            }
+            b':' if depth == 0 && at(i + 1) == Some(b':') => i += 1,
+            b':' if depth == 0 => break i,
+            b'=' if depth == 0 => {
+                let operator = matches!(at(i + 1), Some(b'=' | b'>'))
+                    || (i > start && matches!(bytes[i - 1], b'=' | b'!' | b'<' | b'>'));
+                let after = s[i + 1..].trim_start();
+                if !operator && (after.starts_with(['}', '!', ':']) || after.is_empty()) {
+                    break i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    };
+    let expr = s[start..expr_end].trim();
+    if expr.is_empty() {
+        return Err(String::from("f-string: empty expression not allowed"));
+    }
+    let mut i = expr_end;
+    let mut label = None;
+    if at(i) == Some(b'=') {
+        let rest = &s[i + 1..];
+        i = s.len() - rest.trim_start().len();
+        label = Some(&s[start..i]);
+    }
+    let mut conversion = None;
+    if at(i) == Some(b'!') {
+        let c = s[i + 1..].chars().next().ok_or("f-string: expecting '}'")?;
+        if c != 'r' && c != 's' {
+            return Err(format!("f-string: invalid conversion character '{c}': expected 'r' or 's'"));
+        }
+        conversion = Some(c);
+        i += 1 + c.len_utf8();
+        if !matches!(at(i), Some(b':' | b'}')) {
+            return Err(String::from("f-string: expecting ':' or '}' after conversion"));
+        }
+    }
+    let mut spec = None;
+    if at(i) == Some(b':') {
+        let spec_start = i + 1;
+        let mut depth = 0usize;
+        i = spec_start;
+        loop {
+            match at(i) {
+                None => return Err(String::from("f-string: expecting '}'")),
+                Some(b'{') => depth += 1,
+                Some(b'}') if depth == 0 => break,
+                Some(b'}') => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        spec = Some(&s[spec_start..i]);
+    }
+    Ok((Field { expr, label, conversion, spec }, i))
+}
+
+/// Checks the parts of a format spec that do not depend on the value's type,
+/// with the messages of `FormatSpec::parse` in `pyprint`.
+///
+/// `as_str` is set for `!r` and `!s` fields, whose value is always formatted
+/// as a string, so the checks for string specs apply as well.
+fn check_spec(spec: &str, as_str: bool) -> Result<(), String> {
+    let chars: Vec<char> = spec.chars().collect();
+    let at = |i: usize| chars.get(i).copied();
+    let is_align = |c: char| matches!(c, '<' | '>' | '=' | '^');
+    let mut i = 0;
+    let mut align = None;
+    if at(1).is_some_and(is_align) {
+        align = at(1);
+        i = 2;
+    } else if at(0).is_some_and(is_align) {
+        align = at(0);
+        i = 1;
+    }
+    let sign = matches!(at(i), Some('+' | '-' | ' '));
+    i += usize::from(sign);
+    let alternate = at(i) == Some('#');
+    i += usize::from(alternate);
+    while at(i).is_some_and(|c| c.is_ascii_digit()) {
+        i += 1;
+    }
+    let grouping = at(i).filter(|c| matches!(c, ',' | '_'));
+    i += usize::from(grouping.is_some());
+    if at(i) == Some('.') {
+        i += 1;
+        if !at(i).is_some_and(|c| c.is_ascii_digit()) {
+            return Err(String::from("f-string: Format specifier missing precision"));
+        }
+        while at(i).is_some_and(|c| c.is_ascii_digit()) {
+            i += 1;
+        }
+    }
+    if i + 1 < chars.len() {
+        return Err(format!("f-string: Invalid format specifier '{spec}'"));
+    }
+    let ty = at(i);
+    if let Some(code) = ty.filter(|&c| !"bcdeEfFgGnosxX%".contains(c)) {
+        return Err(format!("f-string: Unknown format code '{code}'"));
+    }
+    if !as_str {
+        return Ok(());
+    }
+    if let Some(code) = ty.filter(|&c| c != 's') {
+        return Err(format!("f-string: Unknown format code '{code}' for object of type 'str'"));
+    }
+    if sign {
+        return Err(String::from("f-string: Sign not allowed in string format specifier"));
+    }
+    if alternate {
+        return Err(String::from("f-string: Alternate form (#) not allowed in string format specifier"));
+    }
+    if align == Some('=') {
+        return Err(String::from("f-string: '=' alignment not allowed in string format specifier"));
+    }
+    if let Some(g) = grouping {
+        return Err(format!("f-string: Cannot specify '{g}' with 's'."));
+    }
+    Ok(())
+}
+
+/// Reads the value of a string literal token, undoing escapes.
+fn literal_value(lit: &Literal) -> Result<String, String> {
+    let repr = lit.to_string();
+    if let Some(raw) = repr.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return Ok(String::from(&raw[hashes + 1..raw.len() - hashes - 1]));
+    }
+    let Some(body) = repr.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+        return Err(String::from("expected a string literal"));
+    };
+    let mut out = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('0') => out.push('\0'),
+            Some(c @ ('\\' | '\'' | '"')) => out.push(c),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                out.push(u8::from_str_radix(&hex, 16).map_err(|e| e.to_string())? as char);
+            }
+            Some('u') => {
+                let hex: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                let code = u32::from_str_radix(&hex, 16).map_err(|e| e.to_string())?;
+                out.push(char::from_u32(code).ok_or("invalid unicode escape")?);
+            }
+            Some('\n') => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            }
+            other => return Err(format!("unknown escape: \\{}", other.unwrap_or(' '))),
+        }
+    }
+    Ok(out)
+}
+
+/// Escapes `{` and `}` for use in a Rust format string.
+fn escape_braces(s: &str) -> String {
+    s.replace('{', "{{").replace('}', "}}")
+}
+
+fn punct(c: char) -> TokenTree {
+    TokenTree::Punct(Punct::new(c, Spacing::Alone))
+}
+
+/// Gives every token the span of the f-string, so names resolve at the caller.
+fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            if let TokenTree::Group(g) = &token {
+                token = TokenTree::Group(Group::new(g.delimiter(), respan(g.stream(), span)));
+            }
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
+
+fn parse_expr(expr: &str, span: Span) -> Result<TokenStream, String> {
+    let tokens: TokenStream = expr.parse().map_err(|_| format!("f-string: invalid expression `{expr}`"))?;
+    let group = Group::new(Delimiter::Parenthesis, respan(tokens, span));
+    Ok(TokenStream::from(TokenTree::Group(group)))
+}
+
+/// Builds `::core::format_args!(fmt, args...)`, or `$crate::__private::format!` if `owned`.
+fn format_call(krate: &TokenStream, pieces: &[Piece<'_>], owned: bool, span: Span) -> Result<TokenStream, String> {
+    let mut fmt = String::new();
+    let mut args = Vec::new();
+    for piece in pieces {
+        let field = match piece {
+            Piece::Text(text) => {
+                fmt.push_str(&escape_braces(text));
+                continue;
+            }
+            Piece::Field(field) => field,
+        };
+        if let Some(label) = field.label {
+            fmt.push_str(&escape_braces(label));
+        }
+        let expr = parse_expr(field.expr, span)?;
+        match (field.conversion, field.spec) {
+            (Some('r'), None) | (None, None) if field.label.is_some() || field.conversion.is_some() => {
+                fmt.push_str("{:?}");
+                args.push(expr);
+            }
+            (_, None) => {
+                fmt.push_str("{}");
+                args.push(expr);
+            }
+            (conversion, Some(spec)) => {
+                let wrapper = match conversion {
+                    Some('r') => "DebugWithSpec",
+                    Some(_) => "DisplayWithSpec",
+                    None => "WithSpec",
+                };
+                let spec = if spec.contains('{') {
+                    let nested = parse_fstring(spec)?;
+                    let mut call = TokenStream::from(punct('&'));
+                    call.extend(format_call(krate, &nested, true, span)?);
+                    call
+                } else {
+                    check_spec(spec, conversion.is_some())?;
+                    TokenStream::from(TokenTree::Literal(Literal::string(spec)))
+                };
+                let mut wrapped = krate.clone();
+                wrapped.extend(format!("::format::{wrapper}").parse::<TokenStream>().unwrap());
+                let mut inner = TokenStream::from(punct('&'));
+                inner.extend(expr);
+                inner.extend([punct(',')]);
+                inner.extend(spec);
+                wrapped.extend([TokenTree::Group(Group::new(Delimiter::Parenthesis, inner))]);
+                fmt.push_str("{}");
+                args.push(wrapped);
+            }
+        }
+    }
+    let mut call = if owned {
+        let mut path = krate.clone();
+        path.extend("::__private::format!".parse::<TokenStream>().unwrap());
+        path
+    } else {
+        "::core::format_args!".parse::<TokenStream>().unwrap()
+    };
+    let mut inner = TokenStream::from(TokenTree::Literal(Literal::string(&fmt)));
+    for arg in args {
+        inner.extend([punct(',')]);
+        inner.extend(arg);
+    }
+    call.extend([TokenTree::Group(Group::new(Delimiter::Parenthesis, inner))]);
+    Ok(call)
+}
+
+fn compile_error(msg: &str, span: Span) -> TokenStream {
+    let mut lit = Literal::string(msg);
+    lit.set_span(span);
+    // Give every token the span so the error points at it rather than at the macro call
+    let path: TokenStream = "::core::compile_error!".parse().unwrap();
+    let mut out: TokenStream = path
+        .into_iter()
+        .map(|mut t| {
+            t.set_span(span);
+            t
+        })
+        .collect();
+    let mut args = Group::new(Delimiter::Parenthesis, TokenStream::from(TokenTree::Literal(lit)));
+    args.set_span(span);
+    out.extend([TokenTree::Group(args)]);
+    out
+}
+
+/// Expands `fstring!($crate, "f-string")` into `format_args!` for the f-string.
+///
+/// Fields are `{expr}` (Display), `{expr!r}` (Debug), `{expr!s}` (Display),
+/// `{expr:spec}` with a Python format spec, and `{expr=}` for the
+/// self-documenting form, which prints the expression text before the value.
+#[doc(hidden)]
+#[proc_macro]
+pub fn fstring(input: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter();
+    let krate: TokenStream = tokens
+        .by_ref()
+        .take_while(|t| !matches!(t, TokenTree::Punct(p) if p.as_char() == ','))
+        .collect();
+    let lit = match tokens.next() {
+        Some(TokenTree::Literal(lit)) => lit,
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::None => {
+            match g.stream().into_iter().next() {
+                Some(TokenTree::Literal(lit)) => lit,
+                _ => return compile_error("expected a string literal", g.span()),
+            }
+        }
+        other => {
+            let span = other.map_or_else(Span::call_site, |t| t.span());
+            return compile_error("expected a string literal", span);
+        }
+    };
+    let span = lit.span();
+    let result = literal_value(&lit)
+        .and_then(|s| parse_fstring(&s).and_then(|pieces| format_call(&krate, &pieces, false, span)));
+    match result {
+        Ok(tokens) => tokens,
+        Err(msg) => compile_error(&msg, span),
+    }
+}
+
+#[test]
+fn test_parse_fstring() {
+    let field = |expr, label, conversion, spec| Piece::Field(Field { expr, label, conversion, spec });
+    assert_eq!(
+        parse_fstring("total={total:.2f} items={v.len()}").unwrap(),
+        vec![
+            Piece::Text(String::from("total=")),
+            field("total", None, None, Some(".2f")),
+            Piece::Text(String::from(" items=")),
+            field("v.len()", None, None, None),
+        ]
+    );
+    assert_eq!(parse_fstring("{x=}").unwrap(), vec![field("x", Some("x="), None, None)]);
+    assert_eq!(parse_fstring("{ x = !s:>4}").unwrap(), vec![field("x", Some(" x = "), Some('s'), Some(">4"))]);
+    assert_eq!(parse_fstring("{a == b!r}").unwrap(), vec![field("a == b", None, Some('r'), None)]);
+    assert_eq!(parse_fstring("{a != b}").unwrap(), vec![field("a != b", None, None, None)]);
+    assert_eq!(parse_fstring("{std::f64::consts::E:.3}").unwrap(), vec![field("std::f64::consts::E", None, None, Some(".3"))]);
+    assert_eq!(parse_fstring("{m[\"}\"]}").unwrap(), vec![field("m[\"}\"]", None, None, None)]);
+    assert_eq!(parse_fstring("{x:>{w}}").unwrap(), vec![field("x", None, None, Some(">{w}"))]);
+    assert_eq!(parse_fstring("{{a}}").unwrap(), vec![Piece::Text(String::from("{a}"))]);
+    assert!(parse_fstring("{}").is_err());
+    assert!(parse_fstring("{x").is_err());
+    assert!(parse_fstring("x}").is_err());
+    assert!(parse_fstring("{x!a}").is_err());
+    assert_eq!(parse_fstring("{x)}"), Err(String::from("f-string: unmatched ')'")));
+    assert_eq!(parse_fstring("{a[0]]}"), Err(String::from("f-string: unmatched ']'")));
+
+    // Macro calls and char literals are part of the expression
+    assert_eq!(parse_fstring("{vec![1, 2].len()}").unwrap(), vec![field("vec![1, 2].len()", None, None, None)]);
+    assert_eq!(parse_fstring("{foo! ()!r}").unwrap(), vec![field("foo! ()", None, Some('r'), None)]);
+    assert_eq!(parse_fstring("{s.split(':').count():>3}").unwrap(), vec![field("s.split(':').count()", None, None, Some(">3"))]);
+    assert_eq!(parse_fstring("{c == '}'}").unwrap(), vec![field("c == '}'", None, None, None)]);
+    assert_eq!(parse_fstring("{['\\'', '\"', '{'].len()=}").unwrap(), vec![field("['\\'', '\"', '{'].len()", Some("['\\'', '\"', '{'].len()="), None, None)]);
+    assert_eq!(parse_fstring("{x.max('a')}").unwrap(), vec![field("x.max('a')", None, None, None)]);
+}
+
+#[test]
+fn test_check_spec() {
+    for spec in ["", "*^+12,.2f", ">10", "08.3e", "_x", "%", "<5s"] {
+        assert_eq!(check_spec(spec, false), Ok(()), "{spec}");
+    }
+    assert_eq!(check_spec(">10.", false), Err(String::from("f-string: Format specifier missing precision")));
+    assert_eq!(check_spec(".2ff", false), Err(String::from("f-string: Invalid format specifier '.2ff'")));
+    assert_eq!(check_spec("q", false), Err(String::from("f-string: Unknown format code 'q'")));
+    assert_eq!(check_spec("<5s", true), Ok(()));
+    assert!(check_spec("d", true).is_err());
+    assert!(check_spec("+", true).is_err());
+    assert!(check_spec("=5", true).is_err());
+    assert!(check_spec(",", true).is_err());
+}
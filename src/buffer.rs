@@ -0,0 +1,368 @@
+//! Buffered output with flush policies.
+//!
+//! Each print to a `std::io::Write` destination normally ends in one write
+//! call. When printing many lines, [`Buffered`] collects the output of many
+//! prints and writes it in large blocks, flushing as its [`FlushPolicy`]
+//! says. It can be used as the destination of a [`Printer`](crate::Printer)
+//! or [`PrintConfig`](crate::PrintConfig), and writes out whatever is left
+//! when it is dropped. To buffer a file across many macro calls, keep the
+//! buffer in a configuration:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use pyprint::buffer::{Buffered, FlushPolicy};
+//! use pyprint::{pprn, PrintConfig};
+//!
+//! let file = File::create("lines.txt").unwrap();
+//! let mut out = PrintConfig::new().file(Buffered::new(file, FlushPolicy::Never));
+//! for i in 0..1000 {
+//!     pprn!(@out, "line", i);
+//! }
+//! ```
+//!
+//! [`buffer_stdout`] makes the print macros use a process-wide buffer for
+//! stdout, which is flushed according to its policy and, on Unix only, at
+//! process exit:
+//!
+//! ```
+//! use pyprint::buffer::{self, FlushPolicy};
+//! use pyprint::pprn;
+//!
+//! buffer::buffer_stdout(FlushPolicy::Never);
+//! for i in 0..1000 {
+//!     pprn!("line", i);
+//! }
+//! buffer::flush_stdout().unwrap();
+//! ```
+
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::Once;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use crate::sink::Sink;
+use crate::Result;
+
+/// Default capacity of a [`Buffered`] sink.
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// When a [`Buffered`] sink writes out its buffer, besides when it is full,
+/// flushed explicitly or dropped.
+///
+/// Policies are checked at the end of each print.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Only when the buffer is full, flushed or dropped.
+    Never,
+    /// After each print that wrote a newline, like Python's line buffering.
+    Line,
+    /// After each print.
+    Always,
+    /// After a print once the given time has passed since the last flush.
+    Interval(Duration),
+}
+
+/// A sink that buffers the output of many prints for a `std::io::Write`.
+///
+/// The buffer never splits a single write of the printer: when a write does
+/// not fit, the buffer is written out first, and writes longer than the
+/// capacity go straight to the inner writer.
+pub struct Buffered<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    capacity: usize,
+    policy: FlushPolicy,
+    last_flush: Instant,
+    /// Whether the current print wrote a newline, for [`FlushPolicy::Line`].
+    newline: bool,
+}
+
+impl<W: Write> Buffered<W> {
+    /// Creates a buffered sink with a capacity of 8 KiB.
+    pub fn new(inner: W, policy: FlushPolicy) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner, policy)
+    }
+
+    /// Creates a buffered sink with the given capacity in bytes.
+    pub fn with_capacity(capacity: usize, inner: W, policy: FlushPolicy) -> Self {
+        Buffered {
+            inner,
+            buf: Vec::new(),
+            capacity,
+            policy,
+            last_flush: Instant::now(),
+            newline: false,
+        }
+    }
+
+    /// Returns the flush policy.
+    pub fn policy(&self) -> FlushPolicy {
+        self.policy
+    }
+
+    /// Sets the flush policy.
+    pub fn set_policy(&mut self, policy: FlushPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the output that has not been written out yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Writes the buffer to the inner writer. On error, the bytes that were
+    /// not written stay in the buffer.
+    fn write_out(&mut self) -> Result<()> {
+        let mut written = 0;
+        let res = loop {
+            if written == self.buf.len() {
+                break Ok(());
+            }
+            match self.inner.write(&self.buf[written..]) {
+                Ok(0) => break Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.buf.drain(..written);
+        res
+    }
+}
+
+impl<W: Write> Sink for Buffered<W> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        self.newline |= s.contains('\n');
+        if self.buf.len() + s.len() > self.capacity {
+            self.write_out()?;
+            if s.len() > self.capacity {
+                return self.inner.write_all(s.as_bytes());
+            }
+        }
+        self.buf.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.write_out()?;
+        self.last_flush = Instant::now();
+        self.inner.flush()
+    }
+
+    fn end_print(&mut self) -> Result<()> {
+        let newline = mem::take(&mut self.newline);
+        let due = match self.policy {
+            FlushPolicy::Never => false,
+            FlushPolicy::Line => newline,
+            FlushPolicy::Always => true,
+            FlushPolicy::Interval(interval) => self.last_flush.elapsed() >= interval,
+        };
+        if due {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn is_std_stream(&self) -> bool {
+        crate::sink::is_std_stream::<W>()
+    }
+}
+
+impl<W: Write> Drop for Buffered<W> {
+    fn drop(&mut self) {
+        // Errors cannot be reported from here; use `Sink::flush` to see them
+        let _ = self.flush();
+    }
+}
+
+/// Whether the print macros use [`STDOUT`].
+static STDOUT_BUFFERED: AtomicBool = AtomicBool::new(false);
+
+/// The process-wide stdout buffer.
+static STDOUT: Mutex<Option<Buffered<io::Stdout>>> = Mutex::new(None);
+
+thread_local! {
+    /// Set while the current thread writes into [`STDOUT`], so that a print
+    /// from within a `Display` implementation does not wait for itself.
+    static WRITING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Makes prints to stdout go through a process-wide buffer with the given
+/// flush policy, or changes the policy if they already do.
+///
+/// This applies to the print macros and to every [`Printer`](crate::Printer)
+/// without a destination. Output written to stdout by other means, such as
+/// `println!`, is not ordered with buffered output; call [`flush_stdout`]
+/// before it.
+///
+/// # Process exit
+///
+/// On Unix, the buffer is also flushed at process exit, including exits
+/// through `std::process::exit`, but not when the process is killed or
+/// aborts.
+///
+/// On other platforms nothing flushes the buffer at exit: output still in
+/// the buffer when the process exits is lost. Call [`flush_stdout`] or
+/// [`unbuffer_stdout`] before returning from `main` or calling
+/// `std::process::exit`.
+pub fn buffer_stdout(policy: FlushPolicy) {
+    #[cfg(unix)]
+    flush_at_exit();
+    let mut stdout = STDOUT.lock().unwrap_or_else(PoisonError::into_inner);
+    match &mut *stdout {
+        Some(buffered) => buffered.set_policy(policy),
+        None => *stdout = Some(Buffered::new(io::stdout(), policy)),
+    }
+    STDOUT_BUFFERED.store(true, Ordering::Release);
+}
+
+/// Registers [`flush_stdout`] to run at process exit, once.
+///
+/// `std` has no exit hook, and `std::process::exit` runs no destructors, so
+/// this uses `atexit` from the C library, which `std` links on Unix.
+#[cfg(unix)]
+fn flush_at_exit() {
+    static AT_EXIT: Once = Once::new();
+    AT_EXIT.call_once(|| {
+        extern "C" {
+            fn atexit(callback: extern "C" fn()) -> core::ffi::c_int;
+        }
+        extern "C" fn callback() {
+            let _ = flush_stdout();
+        }
+        // SAFETY: `atexit` has this signature in every Unix C library. The
+        // callback does not unwind, as `flush_stdout` does not panic, and
+        // registers no further callbacks.
+        unsafe {
+            atexit(callback);
+        }
+    });
+}
+
+/// Flushes the stdout buffer and makes prints to stdout unbuffered again.
+pub fn unbuffer_stdout() -> Result<()> {
+    STDOUT_BUFFERED.store(false, Ordering::Release);
+    match STDOUT.lock().unwrap_or_else(PoisonError::into_inner).take() {
+        Some(mut buffered) => buffered.flush(),
+        None => Ok(()),
+    }
+}
+
+/// Writes out the stdout buffer, if there is one, and flushes stdout.
+pub fn flush_stdout() -> Result<()> {
+    // At process exit, thread-locals may already be gone
+    if WRITING.try_with(Cell::get).unwrap_or(false) {
+        return Ok(());
+    }
+    match &mut *STDOUT.lock().unwrap_or_else(PoisonError::into_inner) {
+        Some(buffered) => buffered.flush(),
+        None => io::stdout().flush(),
+    }
+}
+
+/// Runs `f` with the stdout buffer, or gives `f` back if stdout is not
+/// buffered or the current thread is already writing into the buffer.
+pub(crate) fn with_stdout_buffer<R, F>(f: F) -> core::result::Result<R, F>
+where
+    F: FnOnce(&mut dyn Sink) -> R,
+{
+    if !STDOUT_BUFFERED.load(Ordering::Acquire) || WRITING.try_with(Cell::get).unwrap_or(true) {
+        return Err(f);
+    }
+    let mut stdout = STDOUT.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(buffered) = stdout.as_mut() else {
+        return Err(f);
+    };
+    WRITING.with(|w| w.set(true));
+    let _writing = WritingGuard;
+    Ok(f(buffered))
+}
+
+/// Clears [`WRITING`] when dropped, also if a `Display` implementation panics.
+struct WritingGuard;
+
+impl Drop for WritingGuard {
+    fn drop(&mut self) {
+        WRITING.with(|w| w.set(false));
+    }
+}
+
+#[test]
+fn test_buffered() {
+    use crate::{pprint, Printer};
+
+    let mut out = Buffered::new(Vec::new(), FlushPolicy::Never);
+    pprint!(file=&mut out, "a", 1).unwrap();
+    pprint!(file=&mut out, "b", end="").unwrap();
+    assert_eq!(out.get_ref(), b"");
+    assert_eq!(out.buffer(), b"a 1\nb");
+    out.set_policy(FlushPolicy::Line);
+    pprint!(file=&mut out, "", end="").unwrap();
+    assert_eq!(out.get_ref(), b"");  // This print wrote no newline
+    out.set_policy(FlushPolicy::Always);
+    pprint!(file=&mut out, "c", end="").unwrap();
+    assert_eq!(out.get_ref(), b"a 1\nbc");
+    out.set_policy(FlushPolicy::Interval(Duration::from_secs(3600)));
+    pprint!(file=&mut out, "d").unwrap();
+    assert_eq!(out.buffer(), b"d\n");
+    out.flush().unwrap();
+    assert_eq!(out.get_ref(), b"a 1\nbcd\n");
+
+    // Writes that do not fit are never split
+    let mut out = Buffered::with_capacity(4, Vec::new(), FlushPolicy::Never);
+    pprint!(file=&mut out, "abc", "defgh", sep="").unwrap();
+    assert_eq!(out.get_ref(), b"abcdefgh");
+    assert_eq!(out.buffer(), b"\n");
+
+    // The line policy only looks at the newlines of the current print
+    let mut out = Buffered::new(Vec::new(), FlushPolicy::Line);
+    pprint!(file=&mut out, "a").unwrap();
+    pprint!(file=&mut out, "b", end="").unwrap();
+    assert_eq!(out.get_ref(), b"a\n");
+    assert_eq!(out.buffer(), b"b");
+
+    // Bytes that could not be written stay in the buffer
+    let mut dst = [0u8; 4];
+    let mut out = Buffered::new(&mut dst[..], FlushPolicy::Never);
+    pprint!(file=&mut out, "abcdef", end="").unwrap();
+    assert!(out.flush().is_err());
+    assert_eq!(out.buffer(), b"ef");
+    drop(out);
+    assert_eq!(&dst, b"abcd");
+
+    // Dropping the printer writes out its buffer
+    let mut file = Vec::new();
+    Printer::new()
+        .set_file(Buffered::new(&mut file, FlushPolicy::Never))
+        .print_args(&[format_args!("x")])
+        .unwrap();
+    assert_eq!(file, b"x\n");
+}
+
+#[test]
+fn test_panicking_display_in_stdout_buffer() {
+    struct Panics;
+    impl core::fmt::Display for Panics {
+        fn fmt(&self, _: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            panic!("display failed")
+        }
+    }
+
+    buffer_stdout(FlushPolicy::Never);
+    let res = std::panic::catch_unwind(|| crate::pprint!(Panics));
+    assert!(res.is_err());
+    // The thread is no longer marked as writing, so its prints use the buffer again
+    assert!(!WRITING.with(Cell::get));
+    assert!(with_stdout_buffer(|_| ()).is_ok());
+    unbuffer_stdout().unwrap();
+}
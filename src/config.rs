@@ -0,0 +1,192 @@
+//! Reusable printer configurations.
+//!
+//! A [`PrintConfig`] holds the options of a print call (`sep`, `end`, `file`,
+//! `flush`, `limit` and, with `std`, `broken_pipe`) so they can be set up once
+//! and used for many prints,
+//! either with [`PrintConfig::print`] and [`args!`](crate::args) or by naming
+//! the configuration as the first argument of a print macro:
+//!
+//! ```
+//! use pyprint::{args, pprn, PrintConfig};
+//!
+//! let mut csv = PrintConfig::new().sep(",").file(String::new());
+//! pprn!(@csv, "a", "b", "c");
+//! pprn!(@csv, 1, 2, 3, end=";\n");  // Options given to the macro override the configuration
+//! csv.print(args!(4, ?"e", 6.5 => ".2")).unwrap();
+//! assert_eq!(csv.file_ref().unwrap().0, "a,b,c\n1,2,3;\n4,\"e\",6.50\n");
+//! ```
+//!
+//! A configuration is `Clone` whenever its destination is. With the `std`
+//! feature, a configuration can also be made the default for all print macros
+//! (see `set_defaults`).
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use crate::error::BrokenPipePolicy;
+use crate::sink::{IntoSink, Sink};
+use crate::{Element, Printer, Result};
+
+/// The destination type of a [`PrintConfig`] without a file, which prints to
+/// the default output.
+///
+/// This type has no values.
+#[derive(Clone, Copy, Debug)]
+pub enum DefaultFile {}
+
+impl Sink for DefaultFile {
+    fn write_str(&mut self, _s: &str) -> Result<()> {
+        match *self {}
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match *self {}
+    }
+}
+
+/// A set of print options that can be reused for many prints.
+///
+/// The options are set with consuming builder methods. Each print borrows
+/// the configuration through [`PrintConfig::printer`], so options passed to a
+/// print macro along with `@config` only apply to that call.
+#[derive(Clone)]
+pub struct PrintConfig<'a, S = DefaultFile> {
+    pub(crate) sep: Cow<'a, str>,
+    pub(crate) end: Cow<'a, str>,
+    pub(crate) file: Option<S>,
+    pub(crate) fls: bool,
+    pub(crate) limit: Option<usize>,
+    #[cfg(feature = "std")]
+    pub(crate) broken_pipe: Option<BrokenPipePolicy>,
+}
+
+impl Default for PrintConfig<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> PrintConfig<'a> {
+    /// Creates a configuration with the defaults of [`Printer::new`].
+    pub fn new() -> Self {
+        PrintConfig {
+            sep: Cow::Borrowed(" "),
+            end: Cow::Borrowed("\n"),
+            file: None,
+            fls: false,
+            limit: None,
+            #[cfg(feature = "std")]
+            broken_pipe: None,
+        }
+    }
+}
+
+impl<'a, S: Sink + 'a> PrintConfig<'a, S> {
+    /// Sets the separator string used between elements.
+    pub fn sep(mut self, sep: impl Into<Cow<'a, str>>) -> Self {
+        self.sep = sep.into();
+        self
+    }
+
+    /// Sets the end string that is printed after all elements.
+    pub fn end(mut self, end: impl Into<Cow<'a, str>>) -> Self {
+        self.end = end.into();
+        self
+    }
+
+    /// Sets whether output should be flushed after each print.
+    pub fn flush(mut self, fls: bool) -> Self {
+        self.fls = fls;
+        self
+    }
+
+    /// Limits the number of items printed from each spread, as
+    /// [`Printer::set_limit`] does.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sets what prints do when the destination is a closed pipe, as
+    /// [`Printer::set_broken_pipe`] does.
+    #[cfg(feature = "std")]
+    pub fn broken_pipe(mut self, policy: BrokenPipePolicy) -> Self {
+        self.broken_pipe = Some(policy);
+        self
+    }
+
+    /// Sets the output destination, which the configuration owns from then on.
+    ///
+    /// Anything accepted by [`Printer::set_file`] works. Pass a mutable
+    /// reference to keep access to the destination outside the configuration.
+    pub fn file<M, F: IntoSink<'a, M>>(self, file: F) -> PrintConfig<'a, F::Sink> {
+        PrintConfig {
+            sep: self.sep,
+            end: self.end,
+            file: Some(file.into_sink()),
+            fls: self.fls,
+            limit: self.limit,
+            #[cfg(feature = "std")]
+            broken_pipe: self.broken_pipe,
+        }
+    }
+
+    /// Returns the output destination, if one was set.
+    pub fn file_ref(&self) -> Option<&S> {
+        self.file.as_ref()
+    }
+
+    /// Returns a printer with these options, writing to this configuration's
+    /// destination.
+    ///
+    /// The printer borrows the configuration, so its options can be changed
+    /// for a single print without affecting later ones.
+    pub fn printer(&mut self) -> Printer<'_> {
+        Printer {
+            elements: Vec::new(),
+            sep: Cow::Borrowed(&self.sep),
+            end: Cow::Borrowed(&self.end),
+            file: self.file.as_mut().map(|file| Box::new(file) as Box<dyn Sink + '_>),
+            fls: self.fls,
+            limit: self.limit,
+            #[cfg(feature = "std")]
+            broken_pipe: self.broken_pipe,
+        }
+    }
+
+    /// Prints `args` with these options, as [`Printer::print_elements`] does.
+    pub fn print(&mut self, args: &[Element<'_>]) -> Result<()> {
+        self.printer().print_elements(args)
+    }
+
+    /// Returns the text that [`PrintConfig::print`] would write, as a `String`.
+    pub fn render(&mut self, args: &[Element<'_>]) -> String {
+        self.printer().render_elements(args)
+    }
+}
+
+#[test]
+fn test_print_config() {
+    use crate::{args, pprn, rprn};
+    use crate::sink::FmtSink;
+
+    let base = PrintConfig::new().sep(", ").end(".\n").limit(2);
+    let mut a = base.clone().file(String::new());
+    let mut b = a.clone();
+    pprn!(@a, "x", *[1, 2, 3]);
+    rprn!(@a, "y", sep="|", end="\n");
+    pprn!(@a);
+    a.print(args!(?'z', 1.5 => ".2", *0..1)).unwrap();
+    b.print(args!()).unwrap();
+    assert_eq!(a.file_ref().map(|f| f.0.as_str()), Some("x, 1, 2, ... 1 more.\n'y'\n.\n'z', 1.50, 0.\n"));
+    assert_eq!(b.file_ref().map(|f| f.0.as_str()), Some(".\n"));
+
+    let mut out = String::new();
+    let mut c = base.sep("").file(FmtSink(&mut out));
+    assert_eq!(c.render(args!("a", "b")), "ab.\n");
+    c.printer().set_end("!").print_elements(args!("c")).unwrap();
+    assert_eq!(out, "c!");
+}
@@ -0,0 +1,204 @@
+//! Default options for the print macros.
+//!
+//! The print macros start from [`Printer::from_defaults`], which takes its
+//! options from the [`PrintConfig`] given to [`set_defaults`], or from the
+//! innermost [`with_defaults`] call on the current thread. Options passed to a
+//! macro still override the defaults, and `pprint!(@config, ...)` and
+//! `sprint!` do not use them.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use pyprint::{pprn, with_defaults, PrintConfig};
+//!
+//! #[derive(Clone, Default)]
+//! struct Log(Arc<Mutex<Vec<u8>>>);
+//!
+//! impl std::io::Write for Log {
+//!     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+//!         self.0.lock().unwrap().write(buf)
+//!     }
+//!
+//!     fn flush(&mut self) -> std::io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let log = Log::default();
+//! with_defaults(PrintConfig::new().end(";\n").file(log.clone()), || {
+//!     pprn!("captured", 1);
+//!     pprn!("also captured", end="\n");
+//! });
+//! assert_eq!(*log.0.lock().unwrap(), b"captured 1;\nalso captured\n");
+//! ```
+
+use std::borrow::Cow;
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::vec::Vec;
+
+use crate::error::BrokenPipePolicy;
+use crate::sink::Sink;
+use crate::{PrintConfig, Printer, Result};
+
+/// The options of a [`PrintConfig`], with the destination shared by all prints.
+pub(crate) struct Defaults {
+    sep: Cow<'static, str>,
+    end: Cow<'static, str>,
+    file: Option<Arc<Mutex<dyn Sink + Send>>>,
+    fls: bool,
+    limit: Option<usize>,
+    broken_pipe: Option<BrokenPipePolicy>,
+}
+
+impl Defaults {
+    fn new<S: Sink + Send + 'static>(config: PrintConfig<'static, S>) -> Self {
+        let PrintConfig { sep, end, file, fls, limit, broken_pipe } = config;
+        Defaults {
+            sep,
+            end,
+            file: file.map(|file| Arc::new(Mutex::new(file)) as Arc<Mutex<dyn Sink + Send>>),
+            fls,
+            limit,
+            broken_pipe,
+        }
+    }
+
+    pub(crate) fn printer<'a>(&self) -> Printer<'a> {
+        let mut printer = Printer::new();
+        printer.sep = self.sep.clone();
+        printer.end = self.end.clone();
+        printer.file = self
+            .file
+            .as_ref()
+            .map(|file| Box::new(SharedFile(file.clone())) as Box<dyn Sink + 'a>);
+        printer.fls = self.fls;
+        printer.limit = self.limit;
+        printer.broken_pipe = self.broken_pipe;
+        printer
+    }
+}
+
+/// A destination shared by many printers.
+///
+/// It counts as shared, so each print is assembled first and the lock is
+/// only held while the assembled output is written.
+struct SharedFile(Arc<Mutex<dyn Sink + Send>>);
+
+impl Sink for SharedFile {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).write_str(s)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).flush()
+    }
+
+    fn end_print(&mut self) -> Result<()> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).end_print()
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+
+    fn is_std_stream(&self) -> bool {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).is_std_stream()
+    }
+}
+
+/// The process-wide defaults, if set.
+static GLOBAL: RwLock<Option<Arc<Defaults>>> = RwLock::new(None);
+
+thread_local! {
+    /// The defaults of the enclosing `with_defaults` calls, innermost last.
+    static SCOPED: RefCell<Vec<Arc<Defaults>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the defaults in effect on the current thread.
+pub(crate) fn current() -> Option<Arc<Defaults>> {
+    if let Some(scoped) = SCOPED.try_with(|s| s.borrow().last().cloned()).ok().flatten() {
+        return Some(scoped);
+    }
+    GLOBAL.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Sets the options that the print macros start from, in all threads.
+///
+/// The destination, if any, is shared by all prints that use the defaults;
+/// each print is still written as one unit. `set_defaults(PrintConfig::new())`
+/// restores the built-in defaults.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use pyprint::{pprn, set_defaults, PrintConfig};
+///
+/// set_defaults(PrintConfig::new().file(File::create("run.log").unwrap()));
+/// pprn!("this goes to run.log");
+/// ```
+pub fn set_defaults<S: Sink + Send + 'static>(config: PrintConfig<'static, S>) {
+    *GLOBAL.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(Defaults::new(config)));
+}
+
+/// Runs `f` with `config` as the defaults of the print macros on the current
+/// thread, in place of those set with [`set_defaults`].
+///
+/// Calls can be nested; the innermost one applies. The previous defaults are
+/// restored when `f` returns or panics. Threads spawned by `f` do not inherit
+/// the defaults.
+pub fn with_defaults<S, R>(config: PrintConfig<'static, S>, f: impl FnOnce() -> R) -> R
+where
+    S: Sink + Send + 'static,
+{
+    struct Restore;
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED.with(|s| s.borrow_mut().pop());
+        }
+    }
+
+    SCOPED.with(|s| s.borrow_mut().push(Arc::new(Defaults::new(config))));
+    let _restore = Restore;
+    f()
+}
+
+#[test]
+fn test_defaults() {
+    use std::string::String;
+
+    use crate::pretty::PrettyPrinter;
+    use crate::{dprn, pprn, sprint, FmtSink};
+
+    struct Shared(Arc<Mutex<String>>);
+
+    impl Sink for Shared {
+        fn write_str(&mut self, s: &str) -> Result<()> {
+            self.0.lock().unwrap().push_str(s);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    let outer = Arc::new(Mutex::new(String::new()));
+    let inner = Arc::new(Mutex::new(String::new()));
+    with_defaults(PrintConfig::new().sep(", ").file(Shared(outer.clone())), || {
+        pprn!(1, 2);
+        with_defaults(PrintConfig::new().end(";").limit(1).file(Shared(inner.clone())), || {
+            dprn!("a", *[1, 2]);
+            pprn!(3, end="!");
+            assert_eq!(sprint!(4, 5), "4 5");
+        });
+        pprn!(6, 7, sep="-");
+        PrettyPrinter::new().pprint(&[8, 9]).unwrap();
+        let mut local = String::new();
+        pprn!(8, file=FmtSink(&mut local));
+        assert_eq!(local, "8\n");
+    });
+    assert_eq!(*outer.lock().unwrap(), "1, 2\n6-7\n[8, 9]\n");
+    assert_eq!(*inner.lock().unwrap(), "\"a\" 1 ... 1 more;3!");
+    assert!(SCOPED.with(|s| s.borrow().is_empty()));
+}
@@ -0,0 +1,179 @@
+//! Arguments that expand into several printed elements.
+//!
+//! [`Printer::print_elements`](crate::Printer::print_elements) takes
+//! [`Element`]s, each of which is either a single value or a [`Spread`]: a
+//! source of any number of values, each printed as a separate element with
+//! the separator in between. The print macros create spreads for arguments
+//! written as `*iterable`, like Python's `print(*iterable)`.
+//!
+//! Spreads are streamed: each item is formatted and written to the output as
+//! it is produced, so printing a huge iterator needs no memory for the items
+//! already printed. [`Printer::set_limit`](crate::Printer::set_limit) caps the
+//! number of items printed per spread; the rest are replaced by a final
+//! `... N more` element, or `...` when their number is not known without
+//! consuming them.
+
+use core::cell::Cell;
+use core::fmt;
+
+use crate::Result;
+
+/// An argument of [`Printer::print_elements`](crate::Printer::print_elements).
+#[derive(Clone, Copy)]
+pub enum Element<'a> {
+    /// A single element.
+    One(fmt::Arguments<'a>),
+    /// Any number of elements, separated like ordinary ones.
+    Spread(&'a dyn Spread),
+}
+
+/// A source of elements for [`Element::Spread`].
+pub trait Spread {
+    /// Calls `f` with each element in turn, stopping at the first error.
+    fn for_each(&self, f: &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>) -> Result<()>;
+
+    /// Calls `f` with at most `limit` elements and returns the number of
+    /// elements left out, or `None` if some were left out but their number is
+    /// not known.
+    ///
+    /// The default implementation goes through all elements with
+    /// [`Spread::for_each`] and counts the rest. Sources that may be infinite
+    /// should stop early instead.
+    fn for_each_limited(
+        &self,
+        limit: usize,
+        f: &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>,
+    ) -> Result<Option<usize>> {
+        // Items past the limit are only counted, never formatted
+        let (mut written, mut rest) = (0, 0);
+        self.for_each(&mut |args| {
+            if written < limit {
+                written += 1;
+                f(args)
+            } else {
+                rest += 1;
+                Ok(())
+            }
+        })?;
+        Ok(Some(rest))
+    }
+}
+
+/// Spreads the items of an iterable, formatting each with a closure.
+///
+/// The iterable is consumed the first time the elements are written, one item
+/// at a time. With a limit, iteration stops after the limit and one more item,
+/// which tells whether any were left out; their number is shown if the
+/// iterator's `size_hint` gives it exactly.
+///
+/// # Panics
+///
+/// Writing the elements a second time panics, as the iterable is gone.
+///
+/// # Example
+///
+/// ```
+/// use pyprint::{Element, Printer};
+/// use pyprint::element::Splat;
+///
+/// let squares = Splat::new((1u64..).map(|x| x * x).take(1_000_000), |x, f| f(format_args!("{x}")));
+/// let out = Printer::new().set_limit(3).render_elements(&[Element::Spread(&squares)]);
+/// assert_eq!(out, "1 4 9 ... 999997 more\n");
+///
+/// let naturals = Splat::new(1u64.., |x, f| f(format_args!("{x}")));
+/// let out = Printer::new().set_limit(3).render_elements(&[Element::Spread(&naturals)]);
+/// assert_eq!(out, "1 2 3 ...\n");
+/// ```
+pub struct Splat<I, F> {
+    iter: Cell<Option<I>>,
+    format: F,
+}
+
+impl<I, F> Splat<I, F>
+where
+    I: IntoIterator,
+    F: Fn(I::Item, &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>) -> Result<()>,
+{
+    /// Creates a spread of the items of `iter`, where `format` writes each
+    /// item by passing its `fmt::Arguments` to the given function.
+    pub fn new(iter: I, format: F) -> Self {
+        Splat { iter: Cell::new(Some(iter)), format }
+    }
+
+    fn take_iter(&self) -> I::IntoIter {
+        match self.iter.take() {
+            Some(iter) => iter.into_iter(),
+            None => panic!("the items of a `Splat` can only be written once"),
+        }
+    }
+}
+
+impl<I, F> Spread for Splat<I, F>
+where
+    I: IntoIterator,
+    F: Fn(I::Item, &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>) -> Result<()>,
+{
+    fn for_each(&self, f: &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>) -> Result<()> {
+        for item in self.take_iter() {
+            (self.format)(item, f)?;
+        }
+        Ok(())
+    }
+
+    fn for_each_limited(
+        &self,
+        limit: usize,
+        f: &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>,
+    ) -> Result<Option<usize>> {
+        let mut iter = self.take_iter();
+        for item in iter.by_ref().take(limit) {
+            (self.format)(item, f)?;
+        }
+        Ok(match iter.size_hint() {
+            (low, Some(high)) if low == high => Some(low),
+            _ => iter.next().map_or(Some(0), |_| None),
+        })
+    }
+}
+
+/// An argument that writes zero or more elements.
+pub(crate) trait WriteElements {
+    /// Writes the elements with `f`, at most `limit` of them per spread.
+    fn write_elements(
+        &self,
+        limit: Option<usize>,
+        f: &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>,
+    ) -> Result<()>;
+}
+
+impl WriteElements for fmt::Arguments<'_> {
+    fn write_elements(
+        &self,
+        _limit: Option<usize>,
+        f: &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>,
+    ) -> Result<()> {
+        f(*self)
+    }
+}
+
+impl WriteElements for Element<'_> {
+    fn write_elements(
+        &self,
+        limit: Option<usize>,
+        f: &mut dyn FnMut(fmt::Arguments<'_>) -> Result<()>,
+    ) -> Result<()> {
+        match self {
+            Element::One(args) => f(*args),
+            Element::Spread(spread) => {
+                let Some(limit) = limit else {
+                    return spread.for_each(f);
+                };
+                match spread.for_each_limited(limit, f)? {
+                    Some(0) => Ok(()),
+                    Some(rest) => f(format_args!("... {rest} more")),
+                    None => f(format_args!("...")),
+                }
+            }
+        }
+    }
+}
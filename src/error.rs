@@ -0,0 +1,424 @@
+//! Errors reported by print operations.
+//!
+#![cfg_attr(
+    feature = "std",
+    doc = "With the `std` feature, a print fails with an `io::Error`. If the output \
+           was written but flushing it failed, the error wraps a [`FlushError`], which \
+           [`is_flush_error`] detects; the error kind is that of the original error."
+)]
+#![cfg_attr(not(feature = "std"), doc = "Without the `std` feature, a print fails with a `core::fmt::Error`.")]
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use std::io;
+//! use pyprint::error::is_flush_error;
+//! use pyprint::{pprint, Sink};
+//!
+//! struct Unflushable;
+//!
+//! impl Sink for Unflushable {
+//!     fn write_str(&mut self, _s: &str) -> pyprint::Result<()> {
+//!         Ok(())
+//!     }
+//!
+//!     fn flush(&mut self) -> pyprint::Result<()> {
+//!         Err(io::Error::other("device gone"))
+//!     }
+//! }
+//!
+//! let err = pprint!("done", flush=true, file=Unflushable).unwrap_err();
+//! assert!(is_flush_error(&err));
+//! assert_eq!(err.to_string(), "flush failed: device gone");
+//! # }
+//! ```
+//!
+#![cfg_attr(
+    feature = "std",
+    doc = "The `try_*` macros, such as [`try_pprint!`](crate::try_pprint), return a \
+           [`PrintError`] instead, which sorts the failure into a few kinds and \
+           records the macro call that failed. It implements `std::error::Error`, so \
+           `?` converts it into boxed errors:"
+)]
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use pyprint::try_pprint;
+//!
+//! fn report(total: f64) -> Result<(), Box<dyn std::error::Error>> {
+//!     try_pprint!("total:", total)?;
+//!     Ok(())
+//! }
+//! # report(1.5).unwrap();
+//! # }
+//! ```
+//!
+//! The `*prn!` macros include the same information when they panic.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU8, Ordering};
+#[cfg(feature = "std")]
+use std::{error, io};
+
+use crate::Error;
+
+/// The print macro call that an error comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Context {
+    /// The name of the macro, such as `"pprn!"`.
+    pub macro_name: &'static str,
+    /// The source file of the call.
+    pub file: &'static str,
+    /// The line of the call.
+    pub line: u32,
+}
+
+impl Context {
+    /// Creates a context; the macros pass `file!()` and `line!()`.
+    pub const fn new(macro_name: &'static str, file: &'static str, line: u32) -> Self {
+        Context { macro_name, file, line }
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}", self.macro_name, self.file, self.line)
+    }
+}
+
+/// A failed print, sorted by cause, with the macro call it comes from.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PrintError {
+    /// The destination reported an I/O error not covered by other variants.
+    Io(io::Error, Context),
+    /// A `Display` or `Debug` implementation of an argument returned an
+    /// error, or a `core::fmt::Write` destination did.
+    Format(io::Error, Context),
+    /// The destination rejected the output as invalid data, as a Windows
+    /// console does for text it cannot represent.
+    Encoding(io::Error, Context),
+    /// The destination was closed, like a pipe whose reader has exited.
+    Closed(io::Error, Context),
+    /// The output was written, but flushing it failed. The error wraps a
+    /// [`FlushError`] with the original error.
+    Flush(io::Error, Context),
+}
+
+#[cfg(feature = "std")]
+impl PrintError {
+    /// Sorts `err` into a variant. A failed flush is always
+    /// [`PrintError::Flush`], whatever the kind of the original error.
+    pub fn new(err: io::Error, context: Context) -> Self {
+        if is_flush_error(&err) {
+            return PrintError::Flush(err, context);
+        }
+        match err.kind() {
+            _ if err.get_ref().is_some_and(|inner| inner.is::<FormatFailed>()) => PrintError::Format(err, context),
+            io::ErrorKind::InvalidData => PrintError::Encoding(err, context),
+            io::ErrorKind::BrokenPipe | io::ErrorKind::WriteZero => PrintError::Closed(err, context),
+            _ => PrintError::Io(err, context),
+        }
+    }
+
+    /// Returns the macro call that failed.
+    pub fn context(&self) -> &Context {
+        match self {
+            PrintError::Io(_, context)
+            | PrintError::Format(_, context)
+            | PrintError::Encoding(_, context)
+            | PrintError::Closed(_, context)
+            | PrintError::Flush(_, context) => context,
+        }
+    }
+
+    /// Returns the underlying I/O error.
+    pub fn io_error(&self) -> &io::Error {
+        match self {
+            PrintError::Io(err, _)
+            | PrintError::Format(err, _)
+            | PrintError::Encoding(err, _)
+            | PrintError::Closed(err, _)
+            | PrintError::Flush(err, _) => err,
+        }
+    }
+
+    /// Returns the underlying I/O error, dropping the context.
+    pub fn into_io_error(self) -> io::Error {
+        match self {
+            PrintError::Io(err, _)
+            | PrintError::Format(err, _)
+            | PrintError::Encoding(err, _)
+            | PrintError::Closed(err, _)
+            | PrintError::Flush(err, _) => err,
+        }
+    }
+
+    /// Whether the output was written but flushing it failed, that is, whether
+    /// this is [`PrintError::Flush`].
+    pub fn is_flush_error(&self) -> bool {
+        matches!(self, PrintError::Flush(..))
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for PrintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cause = match self {
+            PrintError::Io(..) => "I/O error",
+            PrintError::Format(..) => "formatting failed",
+            PrintError::Encoding(..) => "output could not be encoded",
+            PrintError::Closed(..) => "output closed",
+            PrintError::Flush(..) => "output not flushed",
+        };
+        write!(f, "{}: {}: {}", self.context(), cause, self.io_error())
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for PrintError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(self.io_error())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<PrintError> for io::Error {
+    fn from(err: PrintError) -> Self {
+        err.into_io_error()
+    }
+}
+
+/// Adds the context of the macro call to the result of a print.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn with_context(res: crate::Result<()>, context: Context) -> Result<(), PrintError> {
+    res.map_err(|err| PrintError::new(err, context))
+}
+
+/// Panics with the error and the context of the macro call if the print failed.
+#[doc(hidden)]
+#[track_caller]
+pub fn unwrap_print(res: crate::Result<()>, context: Context) {
+    if let Err(err) = res {
+        #[cfg(feature = "std")]
+        panic!("{}", PrintError::new(err, context));
+        #[cfg(not(feature = "std"))]
+        panic!("{}: {}", context, err);
+    }
+}
+
+/// What a print does when its destination is a pipe whose reader has exited,
+/// as when a program's output is piped into `head`.
+///
+/// Writing to such a pipe fails with `io::ErrorKind::BrokenPipe`. The policy
+/// for prints to stdout and stderr, whether by default, with `file=` or
+/// through a [`PrintConfig`](crate::PrintConfig), is set with
+/// [`set_broken_pipe_policy`]; other destinations propagate the error.
+/// [`Printer::set_broken_pipe`](crate::Printer::set_broken_pipe),
+/// [`PrintConfig::broken_pipe`](crate::PrintConfig::broken_pipe) and the
+/// `broken_pipe=` macro option override it for any destination.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrokenPipePolicy {
+    /// Return the error, so that the `*prn!` macros panic. This is the default.
+    Propagate,
+    /// Exit the process quietly with status 0, like a Python program that
+    /// restores the default `SIGPIPE` handler.
+    Exit,
+    /// Report success and discard the output.
+    Ignore,
+}
+
+#[cfg(feature = "std")]
+static BROKEN_PIPE_POLICY: AtomicU8 = AtomicU8::new(BrokenPipePolicy::Propagate as u8);
+
+/// Sets the broken pipe policy for prints to stdout and stderr.
+///
+/// ```no_run
+/// use pyprint::error::{set_broken_pipe_policy, BrokenPipePolicy};
+/// use pyprint::pprn;
+///
+/// // `program | head -n 1` prints one line and exits quietly
+/// set_broken_pipe_policy(BrokenPipePolicy::Exit);
+/// for i in 0.. {
+///     pprn!(i);
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub fn set_broken_pipe_policy(policy: BrokenPipePolicy) {
+    BROKEN_PIPE_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// Returns the broken pipe policy for prints to stdout and stderr.
+#[cfg(feature = "std")]
+pub fn broken_pipe_policy() -> BrokenPipePolicy {
+    match BROKEN_PIPE_POLICY.load(Ordering::Relaxed) {
+        x if x == BrokenPipePolicy::Exit as u8 => BrokenPipePolicy::Exit,
+        x if x == BrokenPipePolicy::Ignore as u8 => BrokenPipePolicy::Ignore,
+        _ => BrokenPipePolicy::Propagate,
+    }
+}
+
+/// Applies `policy` to the result of a print.
+#[cfg(feature = "std")]
+pub(crate) fn handle_broken_pipe(res: crate::Result<()>, policy: BrokenPipePolicy) -> crate::Result<()> {
+    match res {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => match policy {
+            BrokenPipePolicy::Propagate => Err(err),
+            BrokenPipePolicy::Exit => std::process::exit(0),
+            BrokenPipePolicy::Ignore => Ok(()),
+        },
+        res => res,
+    }
+}
+
+/// The error of a `Display` implementation or `core::fmt::Write` destination
+/// that failed, inside an `io::Error`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct FormatFailed;
+
+#[cfg(feature = "std")]
+impl fmt::Display for FormatFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an error occurred when formatting an argument")
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for FormatFailed {}
+
+/// The error of a failed flush, after the output was written successfully.
+///
+/// Print operations return it wrapped in an `io::Error` of the same kind.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FlushError(io::Error);
+
+#[cfg(feature = "std")]
+impl FlushError {
+    /// Returns the error reported by the destination.
+    pub fn io_error(&self) -> &io::Error {
+        &self.0
+    }
+
+    /// Returns the error reported by the destination.
+    pub fn into_io_error(self) -> io::Error {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for FlushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "flush failed: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for FlushError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Whether `err` happened while flushing, rather than while writing.
+#[cfg(feature = "std")]
+pub fn is_flush_error(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|inner| inner.is::<FlushError>())
+}
+
+/// Marks `err` as a flush error.
+#[cfg(feature = "std")]
+pub(crate) fn flush_error(err: Error) -> Error {
+    if is_flush_error(&err) {
+        return err;
+    }
+    io::Error::new(err.kind(), FlushError(err))
+}
+
+/// Marks `err` as a flush error, which `core::fmt::Error` cannot record.
+#[cfg(not(feature = "std"))]
+pub(crate) fn flush_error(err: Error) -> Error {
+    err
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_print_error() {
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    use crate::{try_deprint, try_dprint, try_eprint, try_evprint, try_pdeprint, try_pprint};
+
+    struct Failing;
+
+    impl fmt::Display for Failing {
+        fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
+    struct Invalid;
+
+    impl io::Write for Invalid {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "not unicode"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    let (mut bytes, mut text, mut full) = (Vec::new(), String::new(), [0u8; 0]);
+    let line = line!() + 1;
+    let err = try_pprint!("x", Failing, file=&mut bytes).unwrap_err();
+    assert!(matches!(err, PrintError::Format(..)));
+    assert_eq!(*err.context(), Context::new("try_pprint!", file!(), line));
+    assert_eq!(
+        err.to_string(),
+        std::format!("try_pprint! at {}:{line}: formatting failed: an error occurred when formatting an argument", file!())
+    );
+    let err = try_pprint!(Failing, file=&mut text).unwrap_err();
+    assert!(matches!(err, PrintError::Format(..)));
+    let err = try_dprint!("x", file=Invalid).unwrap_err();
+    assert!(matches!(err, PrintError::Encoding(..)));
+    let err = try_pprint!(file=&mut full[..]).unwrap_err();
+    assert!(matches!(err, PrintError::Closed(..)));
+    assert!(try_pprint!(file=&mut bytes).is_ok());
+    let err = io::Error::from(try_pprint!(file=&mut full[..]).unwrap_err());
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+
+    // A failed flush is reported as such, whatever its cause
+    let mut file = crate::FlushCounter::new();
+    file.fail_flush = true;
+    let err = try_pprint!("x", flush=true, file=&mut file).unwrap_err();
+    assert!(matches!(err, PrintError::Flush(..)) && err.is_flush_error());
+    assert!(err.to_string().contains(": output not flushed: flush failed: "), "{err}");
+
+    // The stderr variants record their own name
+    let line = line!() + 1;
+    let err = try_eprint!("x", Failing, file=&mut bytes).unwrap_err();
+    assert!(matches!(err, PrintError::Format(..)));
+    assert_eq!(*err.context(), Context::new("try_eprint!", file!(), line));
+    let err = try_deprint!("x", file=Invalid).unwrap_err();
+    assert!(matches!(err, PrintError::Encoding(..)));
+    assert_eq!(err.context().macro_name, "try_deprint!");
+    let err = try_pdeprint!([1, 2], file=&mut full[..]).unwrap_err();
+    assert!(matches!(err, PrintError::Closed(..)));
+    assert_eq!(err.context().macro_name, "try_pdeprint!");
+    let x = 1;
+    assert!(try_evprint!(x, file=&mut text).is_ok());
+    assert_eq!(text, "x=1\n");
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[should_panic(expected = "pprn! at src/error.rs")]
+fn test_prn_panic_context() {
+    let mut full = [0u8; 0];
+    crate::pprn!("x", file=&mut full[..]);
+}
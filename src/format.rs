@@ -0,0 +1,1201 @@
+//! Python-style runtime formatting: `str.format` and the `%` operator.
+//!
+//! [`format()`] implements `str.format` replacement fields (`{}`, `{0}`, `{name}`,
+//! `!r`/`!s`/`!a` conversions and nested fields in specs), and [`percent`]
+//! implements printf-style `%` formatting. Both apply Python's format
+//! mini-language ([`FormatSpec`]) to Rust numbers and strings, which are passed
+//! as [`FormatArg`]s. The [`pyformat!`](crate::pyformat) and
+//! [`pypercent!`](crate::pypercent) macros convert their arguments automatically.
+//!
+//! ```
+//! use pyprint::{pyformat, pypercent};
+//!
+//! let (a, b) = (3, "ab");
+//! assert_eq!(pyformat!("{0} is {name:>5}", a, name=b).unwrap(), "3 is    ab");
+//! assert_eq!(pypercent!("%5.2f %s", 1.5, b).unwrap(), " 1.50 ab");
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+/// A value that can be formatted by [`format()`] and [`percent`].
+///
+/// Integers map to Python's `int`, floats to `float`, `bool` to `bool`, and
+/// characters and strings to `str`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatArg<'a> {
+    /// An integer.
+    Int(i128),
+    /// A floating point number.
+    Float(f64),
+    /// A boolean, printed as `True`/`False` and formatted as `1`/`0` with numeric specs.
+    Bool(bool),
+    /// A single character, treated as a one-character string.
+    Char(char),
+    /// A string.
+    Str(&'a str),
+}
+
+impl FormatArg<'_> {
+    /// The name of the corresponding Python type, used in error messages.
+    fn type_name(&self) -> &'static str {
+        match self {
+            FormatArg::Int(_) => "int",
+            FormatArg::Float(_) => "float",
+            FormatArg::Bool(_) => "bool",
+            FormatArg::Char(_) | FormatArg::Str(_) => "str",
+        }
+    }
+
+    /// Python's `str()` of the value.
+    fn to_str(self) -> String {
+        match self {
+            FormatArg::Int(v) => v.to_string(),
+            FormatArg::Float(v) => float_repr(v),
+            FormatArg::Bool(v) => String::from(if v { "True" } else { "False" }),
+            FormatArg::Char(c) => c.to_string(),
+            FormatArg::Str(s) => String::from(s),
+        }
+    }
+
+    /// Python's `repr()` of the value, with non-ASCII characters escaped if `ascii` is set.
+    fn to_repr(self, ascii: bool) -> String {
+        let mut out = String::new();
+        match self {
+            FormatArg::Char(c) => {
+                let mut buf = [0; 4];
+                write_str_repr(&mut out, c.encode_utf8(&mut buf), ascii)
+            }
+            FormatArg::Str(s) => write_str_repr(&mut out, s, ascii),
+            other => return other.to_str(),
+        }
+        .expect("writing to a String cannot fail");
+        out
+    }
+}
+
+/// Conversion into a [`FormatArg`].
+pub trait ToFormatArg {
+    /// Borrows `self` as a format argument.
+    fn to_format_arg(&self) -> FormatArg<'_>;
+}
+
+macro_rules! impl_to_format_arg_int {
+    ($($t:ty)*) => {
+        $(
+            impl ToFormatArg for $t {
+                fn to_format_arg(&self) -> FormatArg<'_> {
+                    FormatArg::Int(*self as i128)
+                }
+            }
+        )*
+    };
+}
+
+impl_to_format_arg_int!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 usize);
+
+impl ToFormatArg for f64 {
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        FormatArg::Float(*self)
+    }
+}
+
+impl ToFormatArg for f32 {
+    /// Converts through the shortest decimal representation, so `0.1f32`
+    /// formats as `0.1` rather than `0.10000000149011612`.
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        let shortest = alloc::format!("{}", self).parse().unwrap_or(f64::from(*self));
+        FormatArg::Float(shortest)
+    }
+}
+
+impl ToFormatArg for bool {
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        FormatArg::Bool(*self)
+    }
+}
+
+impl ToFormatArg for char {
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        FormatArg::Char(*self)
+    }
+}
+
+impl ToFormatArg for str {
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        FormatArg::Str(self)
+    }
+}
+
+impl ToFormatArg for String {
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        FormatArg::Str(self)
+    }
+}
+
+impl<T: ToFormatArg + ?Sized> ToFormatArg for &T {
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        (**self).to_format_arg()
+    }
+}
+
+impl ToFormatArg for FormatArg<'_> {
+    fn to_format_arg(&self) -> FormatArg<'_> {
+        *self
+    }
+}
+
+/// An error from [`format()`], [`percent`] or [`FormatSpec`].
+///
+/// The `Display` output follows the message of the corresponding Python
+/// exception. A missing keyword, for which Python's `KeyError` only gives the
+/// key, is described in the same plain words as the other errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    /// A brace in the format string is not part of a field or an escape.
+    Syntax(&'static str),
+    /// A format spec is malformed or not allowed for the value's type.
+    InvalidSpec(String),
+    /// The type letter of a spec does not apply to the value's type.
+    UnknownFormatCode {
+        /// The type letter.
+        code: char,
+        /// The Python type name of the value.
+        type_name: &'static str,
+    },
+    /// A positional field refers to an argument that was not given.
+    MissingArgument(usize),
+    /// A named field refers to a keyword argument that was not given.
+    MissingKeyword(String),
+    /// Automatic (`{}`) and manual (`{0}`) field numbering were mixed.
+    NumberingSwitch,
+    /// A field uses attribute or index access, which is not supported.
+    UnsupportedField(String),
+    /// A conversion other than `!r`, `!s` or `!a` was used.
+    UnknownConversion(char),
+    /// A `%` conversion needs more arguments than were given.
+    NotEnoughArguments,
+    /// Some arguments were not used by any `%` conversion.
+    TooManyArguments,
+    /// A `%` conversion got a value of the wrong type.
+    TypeMismatch {
+        /// The conversion letter.
+        code: char,
+        /// The Python type name of the value.
+        type_name: &'static str,
+    },
+    /// A `%` conversion uses an unknown conversion letter.
+    UnsupportedCharacter {
+        /// The conversion letter.
+        code: char,
+        /// The byte index of the letter in the format string.
+        index: usize,
+    },
+    /// The format string ends inside a `%` conversion.
+    IncompleteFormat,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Syntax(msg) => f.write_str(msg),
+            FormatError::InvalidSpec(msg) => f.write_str(msg),
+            FormatError::UnknownFormatCode { code, type_name } => {
+                write!(f, "Unknown format code '{code}' for object of type '{type_name}'")
+            }
+            FormatError::MissingArgument(index) => {
+                write!(f, "Replacement index {index} out of range for positional args tuple")
+            }
+            FormatError::MissingKeyword(name) => {
+                write!(f, "no argument named '{name}' for the format string")
+            }
+            FormatError::NumberingSwitch => {
+                f.write_str("cannot switch between automatic field numbering and manual field specification")
+            }
+            FormatError::UnsupportedField(name) => {
+                write!(f, "attribute and index access are not supported in field '{name}'")
+            }
+            FormatError::UnknownConversion(c) => write!(f, "Unknown conversion specifier {c}"),
+            FormatError::NotEnoughArguments => f.write_str("not enough arguments for format string"),
+            FormatError::TooManyArguments => {
+                f.write_str("not all arguments converted during string formatting")
+            }
+            FormatError::TypeMismatch { code: 'c', type_name } => {
+                write!(f, "%c requires an int or a unicode character, not {type_name}")
+            }
+            FormatError::TypeMismatch { code, type_name } => match code {
+                'o' | 'x' | 'X' => write!(f, "%{code} format: an integer is required, not {type_name}"),
+                _ => write!(f, "%{code} format: a real number is required, not {type_name}"),
+            },
+            FormatError::UnsupportedCharacter { code, index } => write!(
+                f,
+                "unsupported format character '{code}' ({:#x}) at index {index}",
+                u32::from(*code)
+            ),
+            FormatError::IncompleteFormat => f.write_str("incomplete format"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FormatError {}
+
+/// A parsed Python format spec:
+/// `[[fill]align][sign][#][0][width][grouping][.precision][type]`.
+///
+/// # Example
+///
+/// ```
+/// use pyprint::format::{FormatArg, FormatSpec};
+///
+/// let spec = FormatSpec::parse("*^+12,.2f").unwrap();
+/// assert_eq!(spec.apply(FormatArg::Float(1234.5)).unwrap(), "*+1,234.50**");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FormatSpec {
+    fill: Option<char>,
+    align: Option<char>,
+    sign: Option<char>,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    grouping: Option<char>,
+    precision: Option<usize>,
+    ty: Option<char>,
+    /// Minimum number of integer digits, used by `%` conversions such as `%.3d`.
+    min_digits: usize,
+}
+
+impl FormatSpec {
+    /// Parses a format spec, the part of a replacement field after the `:`.
+    pub fn parse(spec: &str) -> Result<FormatSpec, FormatError> {
+        let chars: Vec<char> = spec.chars().collect();
+        let mut out = FormatSpec::default();
+        let mut i = 0;
+        let is_align = |c: char| matches!(c, '<' | '>' | '=' | '^');
+        if chars.len() >= 2 && is_align(chars[1]) {
+            out.fill = Some(chars[0]);
+            out.align = Some(chars[1]);
+            i = 2;
+        } else if !chars.is_empty() && is_align(chars[0]) {
+            out.align = Some(chars[0]);
+            i = 1;
+        }
+        if let Some(&c @ ('+' | '-' | ' ')) = chars.get(i) {
+            out.sign = Some(c);
+            i += 1;
+        }
+        if chars.get(i) == Some(&'#') {
+            out.alternate = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            out.zero = true;
+            i += 1;
+        }
+        let (width, next) = parse_number(&chars, i);
+        out.width = width.unwrap_or(0);
+        i = next;
+        if let Some(&c @ (',' | '_')) = chars.get(i) {
+            out.grouping = Some(c);
+            i += 1;
+        }
+        if chars.get(i) == Some(&'.') {
+            let (precision, next) = parse_number(&chars, i + 1);
+            if precision.is_none() {
+                return Err(FormatError::InvalidSpec(String::from("Format specifier missing precision")));
+            }
+            out.precision = precision;
+            i = next;
+        }
+        if i + 1 < chars.len() {
+            return Err(FormatError::InvalidSpec(alloc::format!("Invalid format specifier '{spec}'")));
+        }
+        out.ty = chars.get(i).copied();
+        Ok(out)
+    }
+
+    /// Formats a value according to this spec, like Python's `format(value, spec)`.
+    pub fn apply(&self, arg: FormatArg<'_>) -> Result<String, FormatError> {
+        match arg {
+            FormatArg::Str(s) => self.format_str(s),
+            FormatArg::Char(c) => {
+                let mut buf = [0; 4];
+                self.format_str(c.encode_utf8(&mut buf))
+            }
+            FormatArg::Bool(b) if *self == FormatSpec::default() => {
+                Ok(String::from(if b { "True" } else { "False" }))
+            }
+            FormatArg::Bool(b) => self.format_int(i128::from(b), "bool"),
+            FormatArg::Int(v) => self.format_int(v, "int"),
+            FormatArg::Float(v) => self.format_float(v, "float"),
+        }
+    }
+
+    fn format_str(&self, s: &str) -> Result<String, FormatError> {
+        if let Some(code) = self.ty.filter(|&c| c != 's') {
+            return Err(FormatError::UnknownFormatCode { code, type_name: "str" });
+        }
+        if self.sign.is_some() {
+            return Err(invalid_spec("Sign not allowed in string format specifier"));
+        }
+        if self.alternate {
+            return Err(invalid_spec("Alternate form (#) not allowed in string format specifier"));
+        }
+        if self.align == Some('=') {
+            return Err(invalid_spec("'=' alignment not allowed in string format specifier"));
+        }
+        if let Some(g) = self.grouping {
+            return Err(FormatError::InvalidSpec(alloc::format!("Cannot specify '{g}' with 's'.")));
+        }
+        let body: String = match self.precision {
+            Some(p) => s.chars().take(p).collect(),
+            None => String::from(s),
+        };
+        let fill = self.fill.unwrap_or(if self.zero { '0' } else { ' ' });
+        Ok(pad("", &body, fill, self.align.unwrap_or('<'), self.width))
+    }
+
+    fn format_int(&self, v: i128, type_name: &'static str) -> Result<String, FormatError> {
+        let (radix, prefix, every) = match self.ty {
+            None | Some('d') | Some('n') => (10, "", 3),
+            Some('b') => (2, "0b", 4),
+            Some('o') => (8, "0o", 4),
+            Some('x') => (16, "0x", 4),
+            Some('X') => (16, "0X", 4),
+            Some('c') => return self.format_char(v),
+            Some('e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%') => return self.format_float(v as f64, type_name),
+            Some(code) => return Err(FormatError::UnknownFormatCode { code, type_name }),
+        };
+        if self.precision.is_some() {
+            return Err(invalid_spec("Precision not allowed in integer format specifier"));
+        }
+        if radix != 10 && self.grouping == Some(',') {
+            return Err(FormatError::InvalidSpec(alloc::format!(
+                "Cannot specify ',' with '{}'.",
+                self.ty.unwrap_or('d')
+            )));
+        }
+        let magnitude = v.unsigned_abs();
+        let mut digits = match radix {
+            2 => alloc::format!("{magnitude:b}"),
+            8 => alloc::format!("{magnitude:o}"),
+            16 if self.ty == Some('X') => alloc::format!("{magnitude:X}"),
+            16 => alloc::format!("{magnitude:x}"),
+            _ => magnitude.to_string(),
+        };
+        while digits.len() < self.min_digits {
+            digits.insert(0, '0');
+        }
+        let prefix = if self.alternate { prefix } else { "" };
+        Ok(self.finish_number(v < 0, prefix, &digits, "", every))
+    }
+
+    fn format_char(&self, v: i128) -> Result<String, FormatError> {
+        if self.sign.is_some() {
+            return Err(invalid_spec("Sign not allowed with integer format specifier 'c'"));
+        }
+        if self.alternate {
+            return Err(invalid_spec("Alternate form (#) not allowed with integer format specifier 'c'"));
+        }
+        let c = u32::try_from(v)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| invalid_spec("%c arg not in range(0x110000)"))?;
+        let fill = self.fill.unwrap_or(' ');
+        Ok(pad("", c.encode_utf8(&mut [0; 4]), fill, self.align.unwrap_or('>'), self.width))
+    }
+
+    fn format_float(&self, v: f64, type_name: &'static str) -> Result<String, FormatError> {
+        let upper = matches!(self.ty, Some('E' | 'F' | 'G'));
+        let negative = v.is_sign_negative() && !v.is_nan();
+        let x = abs(v);
+        if !x.is_finite() {
+            let mut body = String::from(if x.is_nan() { "nan" } else { "inf" });
+            match self.ty {
+                None | Some('e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'n' | '%') => {}
+                Some(code) => return Err(FormatError::UnknownFormatCode { code, type_name }),
+            }
+            if upper {
+                body.make_ascii_uppercase();
+            }
+            if self.ty == Some('%') {
+                body.push('%');
+            }
+            return Ok(self.finish_number(negative, "", &body, "", 3));
+        }
+        let body = match self.ty {
+            Some('f' | 'F') => fixed(x, self.precision.unwrap_or(6), self.alternate),
+            Some('e' | 'E') => scientific(x, self.precision.unwrap_or(6), self.alternate, upper),
+            Some('g' | 'G' | 'n') => general(x, self.precision.unwrap_or(6), self.alternate, upper, false),
+            Some('%') => fixed(x * 100.0, self.precision.unwrap_or(6), self.alternate) + "%",
+            None => match self.precision {
+                Some(p) => general(x, p, self.alternate, false, true),
+                None => float_repr(x),
+            },
+            Some(code) => return Err(FormatError::UnknownFormatCode { code, type_name }),
+        };
+        // Grouping only applies to the integer part.
+        let split = body.find(|c: char| !c.is_ascii_digit()).unwrap_or(body.len());
+        let (int_part, rest) = body.split_at(split);
+        Ok(self.finish_number(negative, "", int_part, rest, 3))
+    }
+
+    /// Adds sign, prefix, grouping and padding around the digits of a number.
+    fn finish_number(&self, negative: bool, prefix: &str, digits: &str, rest: &str, every: usize) -> String {
+        let sign = match (negative, self.sign) {
+            (true, _) => "-",
+            (false, Some('+')) => "+",
+            (false, Some(' ')) => " ",
+            _ => "",
+        };
+        let (fill, align) = match (self.fill, self.align, self.zero) {
+            (fill, Some(align), zero) => (fill.unwrap_or(if zero { '0' } else { ' ' }), align),
+            (fill, None, true) => (fill.unwrap_or('0'), '='),
+            (fill, None, false) => (fill.unwrap_or(' '), '>'),
+        };
+        let mut digits = String::from(digits);
+        let mut grouped = group(&digits, self.grouping, every);
+        if let (Some(_), '0', '=') = (self.grouping, fill, align) {
+            // Zero padding is grouped as well, as in `format(1234, '09,')`.
+            let used = sign.len() + prefix.len() + rest.chars().count();
+            while grouped.chars().count() + used < self.width && digits.bytes().all(|b| b.is_ascii_digit()) {
+                digits.insert(0, '0');
+                grouped = group(&digits, self.grouping, every);
+            }
+        }
+        let mut lead = String::from(sign);
+        lead.push_str(prefix);
+        grouped.push_str(rest);
+        pad(&lead, &grouped, fill, align, self.width)
+    }
+}
+
+fn invalid_spec(msg: &str) -> FormatError {
+    FormatError::InvalidSpec(String::from(msg))
+}
+
+/// Parses a decimal number at `chars[i..]`, returning it and the index after it.
+fn parse_number(chars: &[char], mut i: usize) -> (Option<usize>, usize) {
+    let mut value = None;
+    while let Some(d) = chars.get(i).and_then(|c| c.to_digit(10)) {
+        value = Some(value.unwrap_or(0) * 10 + d as usize);
+        i += 1;
+    }
+    (value, i)
+}
+
+/// Inserts `sep` every `every` digits, counting from the right.
+fn group(digits: &str, sep: Option<char>, every: usize) -> String {
+    let Some(sep) = sep else {
+        return String::from(digits);
+    };
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % every == 0 {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Pads `lead` + `body` to `width` characters; `=` alignment pads between them.
+fn pad(lead: &str, body: &str, fill: char, align: char, width: usize) -> String {
+    let len = lead.chars().count() + body.chars().count();
+    let padding = width.saturating_sub(len);
+    let fills = |n: usize| core::iter::repeat(fill).take(n);
+    let mut out = String::new();
+    match align {
+        '<' => {
+            out.push_str(lead);
+            out.push_str(body);
+            out.extend(fills(padding));
+        }
+        '^' => {
+            out.extend(fills(padding / 2));
+            out.push_str(lead);
+            out.push_str(body);
+            out.extend(fills(padding - padding / 2));
+        }
+        '=' => {
+            out.push_str(lead);
+            out.extend(fills(padding));
+            out.push_str(body);
+        }
+        _ => {
+            out.extend(fills(padding));
+            out.push_str(lead);
+            out.push_str(body);
+        }
+    }
+    out
+}
+
+/// Fixed-point notation with `precision` digits after the point.
+fn fixed(x: f64, precision: usize, alternate: bool) -> String {
+    let mut out = alloc::format!("{x:.precision$}");
+    if alternate && precision == 0 {
+        out.push('.');
+    }
+    out
+}
+
+/// The absolute value of `x`; `f64::abs` is only available in `core` since
+/// Rust 1.85.
+fn abs(x: f64) -> f64 {
+    f64::from_bits(x.to_bits() & !(1 << 63))
+}
+
+/// Splits Rust's `{:e}` output into mantissa and exponent.
+fn split_exponent(s: &str) -> (&str, i32) {
+    let (mantissa, exp) = s.split_once('e').expect("`{:e}` output has an exponent");
+    (mantissa, exp.parse().expect("`{:e}` exponent is an integer"))
+}
+
+/// Python's exponent suffix: sign and at least two digits, e.g. `e+05`.
+fn exponent_suffix(exp: i32, upper: bool) -> String {
+    let e = if upper { 'E' } else { 'e' };
+    let sign = if exp < 0 { '-' } else { '+' };
+    alloc::format!("{e}{sign}{:02}", exp.unsigned_abs())
+}
+
+/// Scientific notation with `precision` digits after the point.
+fn scientific(x: f64, precision: usize, alternate: bool, upper: bool) -> String {
+    let formatted = alloc::format!("{x:.precision$e}");
+    let (mantissa, exp) = split_exponent(&formatted);
+    let mut out = String::from(mantissa);
+    if alternate && precision == 0 {
+        out.push('.');
+    }
+    out + &exponent_suffix(exp, upper)
+}
+
+/// General format (`g`), or the type-less float format if `repr_style` is set.
+///
+/// `repr_style` switches to scientific notation one exponent earlier and keeps
+/// at least one digit after the point, like Python's `format(x, '.3')`.
+fn general(x: f64, precision: usize, alternate: bool, upper: bool, repr_style: bool) -> String {
+    let p = precision.max(1);
+    let exp = if x == 0.0 {
+        0
+    } else {
+        split_exponent(&alloc::format!("{x:.prec$e}", prec = p - 1)).1
+    };
+    let limit = if repr_style { p as i32 - 1 } else { p as i32 };
+    let mut out = if (-4..limit).contains(&exp) {
+        alloc::format!("{x:.prec$}", prec = (p as i32 - 1 - exp) as usize)
+    } else {
+        let formatted = alloc::format!("{x:.prec$e}", prec = p - 1);
+        let (mantissa, exp) = split_exponent(&formatted);
+        let mut mantissa = String::from(mantissa);
+        if !alternate {
+            strip_fraction_zeros(&mut mantissa);
+        }
+        return mantissa + &exponent_suffix(exp, upper);
+    };
+    if alternate {
+        if !out.contains('.') {
+            out.push('.');
+        }
+    } else {
+        strip_fraction_zeros(&mut out);
+        if repr_style && !out.contains('.') {
+            out.push_str(".0");
+        }
+    }
+    out
+}
+
+/// Removes trailing zeros after the decimal point, and the point if nothing is left.
+fn strip_fraction_zeros(s: &mut String) {
+    if s.contains('.') {
+        while s.ends_with('0') {
+            s.pop();
+        }
+        if s.ends_with('.') {
+            s.pop();
+        }
+    }
+}
+
+/// Python's `repr()` of a float: the shortest string that round-trips.
+///
+/// Scientific notation is used when the decimal exponent is below -4 or
+/// at least 16, and fixed-point output always contains a `.`.
+///
+/// ```
+/// use pyprint::format::float_repr;
+///
+/// assert_eq!(float_repr(1.0), "1.0");
+/// assert_eq!(float_repr(1e16), "1e+16");
+/// assert_eq!(float_repr(0.0001), "0.0001");
+/// assert_eq!(float_repr(1e-5), "1e-05");
+/// assert_eq!(float_repr(-0.0), "-0.0");
+/// assert_eq!(float_repr(f64::NAN), "nan");
+/// ```
+pub fn float_repr(x: f64) -> String {
+    if x.is_nan() {
+        return String::from("nan");
+    }
+    if x.is_infinite() {
+        return String::from(if x < 0.0 { "-inf" } else { "inf" });
+    }
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    if x == 0.0 {
+        return alloc::format!("{sign}0.0");
+    }
+    let formatted = alloc::format!("{:e}", abs(x));
+    let (mantissa, exp) = split_exponent(&formatted);
+    let digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+    let point = exp + 1;
+    let mut out = String::from(sign);
+    if point > -4 && point <= 16 {
+        if point <= 0 {
+            out.push_str("0.");
+            out.extend(core::iter::repeat('0').take(point.unsigned_abs() as usize));
+            out.push_str(&digits);
+        } else if point as usize >= digits.len() {
+            out.push_str(&digits);
+            out.extend(core::iter::repeat('0').take(point as usize - digits.len()));
+            out.push_str(".0");
+        } else {
+            let (int_part, frac_part) = digits.split_at(point as usize);
+            out.push_str(int_part);
+            out.push('.');
+            out.push_str(frac_part);
+        }
+    } else {
+        out.push_str(mantissa);
+        out.push_str(&exponent_suffix(exp, false));
+    }
+    out
+}
+
+/// Writes Python's `repr()` of a string: quoted, with escapes for control
+/// characters, backslashes and the quote. With `ascii`, non-ASCII characters
+/// are escaped as in Python's `ascii()`.
+pub(crate) fn write_str_repr(out: &mut impl Write, s: &str, ascii: bool) -> fmt::Result {
+    let quote = if s.contains('\'') && !s.contains('"') { '"' } else { '\'' };
+    out.write_char(quote)?;
+    for c in s.chars() {
+        match c {
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if c == quote => write!(out, "\\{c}")?,
+            c if (c as u32) < 0x20 || (0x7f..0xa0).contains(&(c as u32)) => write!(out, "\\x{:02x}", c as u32)?,
+            c if ascii && !c.is_ascii() => match c as u32 {
+                n @ 0..=0xff => write!(out, "\\x{n:02x}")?,
+                n @ 0x100..=0xffff => write!(out, "\\u{n:04x}")?,
+                n => write!(out, "\\U{n:08x}")?,
+            },
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char(quote)
+}
+
+/// Looks up the value of a replacement field in `str.format` style.
+struct Fields<'s, 'a> {
+    args: &'s [FormatArg<'a>],
+    kwargs: &'s [(&'s str, FormatArg<'a>)],
+    next_auto: Option<usize>,
+    manual: bool,
+}
+
+impl<'a> Fields<'_, 'a> {
+    fn get(&mut self, name: &str) -> Result<FormatArg<'a>, FormatError> {
+        if name.is_empty() {
+            if self.manual {
+                return Err(FormatError::NumberingSwitch);
+            }
+            let index = self.next_auto.unwrap_or(0);
+            self.next_auto = Some(index + 1);
+            return self.args.get(index).copied().ok_or(FormatError::MissingArgument(index));
+        }
+        if name.contains(['.', '[']) {
+            return Err(FormatError::UnsupportedField(String::from(name)));
+        }
+        if let Ok(index) = name.parse::<usize>() {
+            if self.next_auto.is_some() {
+                return Err(FormatError::NumberingSwitch);
+            }
+            self.manual = true;
+            return self.args.get(index).copied().ok_or(FormatError::MissingArgument(index));
+        }
+        self.kwargs
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|&(_, value)| value)
+            .ok_or_else(|| FormatError::MissingKeyword(String::from(name)))
+    }
+
+    /// Formats the replacement field `field` (without its braces) into `out`.
+    fn format_field(&mut self, field: &str, out: &mut String) -> Result<(), FormatError> {
+        let name_end = field.find(['!', ':']).unwrap_or(field.len());
+        let (name, mut rest) = field.split_at(name_end);
+        let value = self.get(name)?;
+        let converted;
+        let value = match rest.strip_prefix('!') {
+            Some(conv) => {
+                let mut conv_chars = conv.chars();
+                let c = conv_chars.next().ok_or(FormatError::Syntax("end of string while looking for conversion specifier"))?;
+                rest = conv_chars.as_str();
+                if !(rest.is_empty() || rest.starts_with(':')) {
+                    return Err(FormatError::Syntax("expected ':' after conversion specifier"));
+                }
+                converted = match c {
+                    'r' => value.to_repr(false),
+                    'a' => value.to_repr(true),
+                    's' => value.to_str(),
+                    other => return Err(FormatError::UnknownConversion(other)),
+                };
+                FormatArg::Str(&converted)
+            }
+            None => value,
+        };
+        let spec = rest.strip_prefix(':').unwrap_or("");
+        let spec = if spec.contains('{') {
+            // Nested fields, as in `{:>{width}}`, are formatted with `str()`.
+            let mut expanded = String::new();
+            self.expand(spec, &mut expanded)?;
+            FormatSpec::parse(&expanded)?
+        } else {
+            FormatSpec::parse(spec)?
+        };
+        out.push_str(&spec.apply(value)?);
+        Ok(())
+    }
+
+    /// Expands all replacement fields of `fmt` into `out`.
+    fn expand(&mut self, fmt: &str, out: &mut String) -> Result<(), FormatError> {
+        let mut rest = fmt;
+        while let Some(pos) = rest.find(['{', '}']) {
+            out.push_str(&rest[..pos]);
+            let brace = rest.as_bytes()[pos];
+            let after = &rest[pos + 1..];
+            if after.as_bytes().first() == Some(&brace) {
+                out.push(brace as char);
+                rest = &after[1..];
+                continue;
+            }
+            if brace == b'}' {
+                return Err(FormatError::Syntax("Single '}' encountered in format string"));
+            }
+            let end = matching_brace(after).ok_or(FormatError::Syntax("expected '}' before end of string"))?;
+            self.format_field(&after[..end], out)?;
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(())
+    }
+}
+
+/// Finds the `}` closing a replacement field, skipping nested fields in its spec.
+fn matching_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, b) in s.bytes().enumerate() {
+        match b {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Formats `fmt` like Python's `fmt.format(*args, **kwargs)`.
+///
+/// Fields can be automatic (`{}`), positional (`{0}`) or named (`{name}`),
+/// followed by an optional conversion (`!r`, `!s`, `!a`) and format spec
+/// (`:>8.2f`), which may itself contain nested fields (`{:>{width}}`).
+/// Attribute and index access (`{0.x}`, `{0[1]}`) are not supported.
+///
+/// # Example
+///
+/// ```
+/// use pyprint::format::{format, FormatArg};
+///
+/// let out = format("{0}/{1:03d} {name!r:>6}", &[FormatArg::Int(7), FormatArg::Int(5)],
+///                  &[("name", FormatArg::Str("ab"))]).unwrap();
+/// assert_eq!(out, "7/005   'ab'");
+/// ```
+pub fn format(fmt: &str, args: &[FormatArg<'_>], kwargs: &[(&str, FormatArg<'_>)]) -> Result<String, FormatError> {
+    let mut fields = Fields { args, kwargs, next_auto: None, manual: false };
+    let mut out = String::new();
+    fields.expand(fmt, &mut out)?;
+    Ok(out)
+}
+
+/// Formats `fmt` like Python's `fmt % args`, or `fmt % kwargs` for `%(name)s` keys.
+///
+/// Supports the flags `-+ #0`, `*` widths and precisions, and the conversions
+/// `d i u o x X e E f F g G c r s a %`.
+///
+/// # Example
+///
+/// ```
+/// use pyprint::format::{percent, FormatArg};
+///
+/// let out = percent("%5.2f|%-4s|%#x", &[FormatArg::Float(1.23456), FormatArg::Str("ab"),
+///                   FormatArg::Int(255)], &[]).unwrap();
+/// assert_eq!(out, " 1.23|ab  |0xff");
+/// ```
+pub fn percent(fmt: &str, args: &[FormatArg<'_>], kwargs: &[(&str, FormatArg<'_>)]) -> Result<String, FormatError> {
+    let mut out = String::new();
+    let mut next = 0;
+    let mut used_keys = false;
+    let take = |next: &mut usize| {
+        let arg = args.get(*next).copied().ok_or(FormatError::NotEnoughArguments);
+        *next += 1;
+        arg
+    };
+    let mut chars = fmt.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let mut key = None;
+        if let Some(&(start, '(')) = chars.peek() {
+            let close = fmt[start..].find(')').ok_or(FormatError::Syntax("incomplete format key"))?;
+            key = Some(&fmt[start + 1..start + close]);
+            while chars.next_if(|&(i, _)| i <= start + close).is_some() {}
+        }
+        let mut spec = FormatSpec::default();
+        let mut left = false;
+        while let Some((_, flag)) = chars.next_if(|&(_, c)| matches!(c, '-' | '+' | ' ' | '#' | '0')) {
+            match flag {
+                '-' => left = true,
+                '+' => spec.sign = Some('+'),
+                ' ' if spec.sign.is_none() => spec.sign = Some(' '),
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                _ => {}
+            }
+        }
+        let mut read_number = |chars: &mut core::iter::Peekable<core::str::CharIndices<'_>>, next: &mut usize| {
+            if chars.next_if(|&(_, c)| c == '*').is_some() {
+                return match take(next)? {
+                    FormatArg::Int(n) => {
+                        if n < 0 {
+                            left = true;
+                        }
+                        Ok(Some(n.unsigned_abs() as usize))
+                    }
+                    other => Err(FormatError::InvalidSpec(alloc::format!("* wants int, not {}", other.type_name()))),
+                };
+            }
+            let mut value = None;
+            while let Some((_, d)) = chars.next_if(|&(_, c)| c.is_ascii_digit()) {
+                value = Some(value.unwrap_or(0) * 10 + (d as usize - '0' as usize));
+            }
+            Ok(value)
+        };
+        spec.width = read_number(&mut chars, &mut next)?.unwrap_or(0);
+        if chars.next_if(|&(_, c)| c == '.').is_some() {
+            spec.precision = Some(read_number(&mut chars, &mut next)?.unwrap_or(0));
+        }
+        while chars.next_if(|&(_, c)| matches!(c, 'h' | 'l' | 'L')).is_some() {}
+        let (index, code) = chars.next().ok_or(FormatError::IncompleteFormat)?;
+        if code == '%' {
+            out.push('%');
+            continue;
+        }
+        let arg = match key {
+            Some(key) => {
+                used_keys = true;
+                kwargs
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|&(_, v)| v)
+                    .ok_or_else(|| FormatError::MissingKeyword(String::from(key)))?
+            }
+            None => take(&mut next)?,
+        };
+        spec.align = Some(if left {
+            '<'
+        } else if spec.zero && !matches!(code, 's' | 'r' | 'a' | 'c') {
+            '='
+        } else {
+            '>'
+        });
+        if left || matches!(code, 's' | 'r' | 'a' | 'c') {
+            spec.zero = false;
+        }
+        let mismatch = |arg: FormatArg<'_>| FormatError::TypeMismatch { code, type_name: arg.type_name() };
+        let text;
+        let value = match code {
+            'd' | 'i' | 'u' | 'o' | 'x' | 'X' => {
+                let v = match arg {
+                    FormatArg::Int(v) => v,
+                    FormatArg::Bool(b) => i128::from(b),
+                    FormatArg::Float(f) if matches!(code, 'd' | 'i' | 'u') => {
+                        if !f.is_finite() {
+                            return Err(invalid_spec("cannot convert float infinity or NaN to integer"));
+                        }
+                        f as i128
+                    }
+                    other => return Err(mismatch(other)),
+                };
+                spec.ty = Some(if matches!(code, 'i' | 'u') { 'd' } else { code });
+                spec.min_digits = spec.precision.take().unwrap_or(0);
+                FormatArg::Int(v)
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                spec.ty = Some(code);
+                match arg {
+                    FormatArg::Int(v) => FormatArg::Float(v as f64),
+                    FormatArg::Bool(b) => FormatArg::Float(f64::from(u8::from(b))),
+                    FormatArg::Float(v) => FormatArg::Float(v),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            'c' => match arg {
+                FormatArg::Int(v) => {
+                    spec.ty = Some('c');
+                    FormatArg::Int(v)
+                }
+                FormatArg::Char(c) => FormatArg::Char(c),
+                FormatArg::Str(s) if s.chars().count() == 1 => FormatArg::Str(s),
+                other => return Err(mismatch(other)),
+            },
+            's' | 'r' | 'a' => {
+                text = match code {
+                    's' => arg.to_str(),
+                    'r' => arg.to_repr(false),
+                    _ => arg.to_repr(true),
+                };
+                FormatArg::Str(&text)
+            }
+            code => return Err(FormatError::UnsupportedCharacter { code, index }),
+        };
+        out.push_str(&spec.apply(value)?);
+    }
+    if !used_keys && next < args.len() {
+        return Err(FormatError::TooManyArguments);
+    }
+    Ok(out)
+}
+
+/// Displays a value formatted with a Python format spec, for `{x:spec}` fields of `fprint!`.
+#[doc(hidden)]
+pub struct WithSpec<'a, T: ?Sized>(pub &'a T, pub &'a str);
+
+impl<T: ToFormatArg + ?Sized> fmt::Display for WithSpec<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_with_spec(f, self.0.to_format_arg(), self.1)
+    }
+}
+
+/// Displays the `Display` output of a value with a Python format spec, for `{x!s:spec}`.
+#[doc(hidden)]
+pub struct DisplayWithSpec<'a, T: ?Sized>(pub &'a T, pub &'a str);
+
+impl<T: fmt::Display + ?Sized> fmt::Display for DisplayWithSpec<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_with_spec(f, FormatArg::Str(&self.0.to_string()), self.1)
+    }
+}
+
+/// Displays the `Debug` output of a value with a Python format spec, for `{x!r:spec}`.
+#[doc(hidden)]
+pub struct DebugWithSpec<'a, T: ?Sized>(pub &'a T, pub &'a str);
+
+impl<T: fmt::Debug + ?Sized> fmt::Display for DebugWithSpec<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_with_spec(f, FormatArg::Str(&alloc::format!("{:?}", self.0)), self.1)
+    }
+}
+
+fn write_with_spec(f: &mut fmt::Formatter<'_>, arg: FormatArg<'_>, spec: &str) -> fmt::Result {
+    let text = FormatSpec::parse(spec).and_then(|spec| spec.apply(arg)).map_err(|_| fmt::Error)?;
+    f.write_str(&text)
+}
+
+// Splits the arguments of `pyformat!` and `pypercent!` into positional and keyword arguments
+#[doc(hidden)]
+#[macro_export]
+macro_rules! format_variants {
+    (@process [$func:ident, $fmt:expr, [$($args:expr,)*], [$($name:ident = $value:expr,)*]] []) => {
+        $crate::format::$func(
+            $fmt,
+            &[$($crate::format::ToFormatArg::to_format_arg(&$args)),*],
+            &[$((::core::stringify!($name), $crate::format::ToFormatArg::to_format_arg(&$value))),*],
+        )
+    };
+
+    (@process [$func:ident, $fmt:expr, $args:tt, [$($kwargs:tt)*]] [$name:ident = $value:expr, $($rest:tt)*]) => {
+        $crate::format_variants!(@process [$func, $fmt, $args, [$($kwargs)* $name = $value,]] [$($rest)*])
+    };
+
+    (@process [$func:ident, $fmt:expr, [$($args:tt)*], $kwargs:tt] [$e:expr, $($rest:tt)*]) => {
+        $crate::format_variants!(@process [$func, $fmt, [$($args)* $e,], $kwargs] [$($rest)*])
+    };
+
+    (@process [$func:ident, $fmt:expr, $args:tt, $kwargs:tt] [, $($rest:tt)*]) => {
+        $crate::format_variants!(@process [$func, $fmt, $args, $kwargs] [$($rest)*])
+    };
+}
+
+/// Formats a string at runtime like Python's `str.format`.
+///
+/// Positional arguments come first, keyword arguments are written
+/// `name=value`. Returns `Result<String, FormatError>`; see [`format::format`](crate::format::format).
+///
+/// # Examples
+///
+/// ```
+/// use pyprint::pyformat;
+///
+/// let (a, b) = (3, "ab");
+/// assert_eq!(pyformat!("{0} is {name:>5}", a, name=b).unwrap(), "3 is    ab");
+/// assert_eq!(pyformat!("{:,} {:.1%} {!r}", 1234567, 0.256, "x").unwrap(), "1,234,567 25.6% 'x'");
+/// assert!(pyformat!("{:d}", "text").is_err());
+/// ```
+#[macro_export]
+macro_rules! pyformat {
+    ($fmt:expr $(, $($t:tt)*)?) => {
+        $crate::format_variants!(@process [format, $fmt, [], []] [$($($t)*)?,])
+    };
+}
+
+/// Formats a string at runtime like Python's `%` operator.
+///
+/// `pypercent!("%5.2f %s", x, y)` corresponds to `"%5.2f %s" % (x, y)` and
+/// `pypercent!("%(n)d", n=1)` to `"%(n)d" % {"n": 1}`. Returns
+/// `Result<String, FormatError>`; see [`format::percent`](crate::format::percent).
+///
+/// # Examples
+///
+/// ```
+/// use pyprint::pypercent;
+///
+/// assert_eq!(pypercent!("%5.2f %s", 1.5, "ab").unwrap(), " 1.50 ab");
+/// assert_eq!(pypercent!("%(n)03d%%", n=7).unwrap(), "007%");
+/// assert!(pypercent!("%d %d", 1).is_err());
+/// ```
+#[macro_export]
+macro_rules! pypercent {
+    ($fmt:expr $(, $($t:tt)*)?) => {
+        $crate::format_variants!(@process [percent, $fmt, [], []] [$($($t)*)?,])
+    };
+}
+
+#[test]
+fn test_format_spec() {
+    let f = |spec: &str, arg: FormatArg<'_>| FormatSpec::parse(spec).and_then(|s| s.apply(arg));
+    assert_eq!(f("", FormatArg::Int(-42)).unwrap(), "-42");
+    assert_eq!(f("+d", FormatArg::Int(42)).unwrap(), "+42");
+    assert_eq!(f(" d", FormatArg::Int(42)).unwrap(), " 42");
+    assert_eq!(f("*^9", FormatArg::Int(42)).unwrap(), "***42****");
+    assert_eq!(f("08.3f", FormatArg::Float(-2.34567)).unwrap(), "-002.346");
+    assert_eq!(f(",", FormatArg::Int(1234567)).unwrap(), "1,234,567");
+    assert_eq!(f("_b", FormatArg::Int(255)).unwrap(), "1111_1111");
+    assert_eq!(f("#x", FormatArg::Int(255)).unwrap(), "0xff");
+    assert_eq!(f("#010X", FormatArg::Int(255)).unwrap(), "0X000000FF");
+    assert_eq!(f("#o", FormatArg::Int(8)).unwrap(), "0o10");
+    assert_eq!(f("09,", FormatArg::Int(1234)).unwrap(), "0,001,234");
+    assert_eq!(f(",.2f", FormatArg::Float(1234567.891)).unwrap(), "1,234,567.89");
+    assert_eq!(f("c", FormatArg::Int(65)).unwrap(), "A");
+    assert_eq!(f(".3e", FormatArg::Float(12345.678)).unwrap(), "1.235e+04");
+    assert_eq!(f("E", FormatArg::Float(0.000123)).unwrap(), "1.230000E-04");
+    assert_eq!(f("g", FormatArg::Float(1234567.0)).unwrap(), "1.23457e+06");
+    assert_eq!(f("g", FormatArg::Float(0.0001)).unwrap(), "0.0001");
+    assert_eq!(f("g", FormatArg::Float(100.0)).unwrap(), "100");
+    assert_eq!(f("#g", FormatArg::Float(100.0)).unwrap(), "100.000");
+    assert_eq!(f(".3", FormatArg::Float(123.0)).unwrap(), "1.23e+02");
+    assert_eq!(f(".3", FormatArg::Float(12.0)).unwrap(), "12.0");
+    assert_eq!(f(".2%", FormatArg::Float(0.1234)).unwrap(), "12.34%");
+    assert_eq!(f("f", FormatArg::Int(2)).unwrap(), "2.000000");
+    assert_eq!(f("", FormatArg::Float(1e16)).unwrap(), "1e+16");
+    assert_eq!(f("", FormatArg::Float(2.0)).unwrap(), "2.0");
+    assert_eq!(f("F", FormatArg::Float(f64::INFINITY)).unwrap(), "INF");
+    assert_eq!(f("+", FormatArg::Float(f64::NAN)).unwrap(), "+nan");
+    assert_eq!(f("", FormatArg::Bool(true)).unwrap(), "True");
+    assert_eq!(f(">3", FormatArg::Bool(true)).unwrap(), "  1");
+    assert_eq!(f(".2", FormatArg::Str("abc")).unwrap(), "ab");
+    assert_eq!(f("^7", FormatArg::Str("abc")).unwrap(), "  abc  ");
+    assert_eq!(f("05", FormatArg::Str("ab")).unwrap(), "ab000");
+    assert_eq!(f("d", FormatArg::Str("ab")), Err(FormatError::UnknownFormatCode { code: 'd', type_name: "str" }));
+    assert_eq!(f("s", FormatArg::Float(1.0)), Err(FormatError::UnknownFormatCode { code: 's', type_name: "float" }));
+    assert!(matches!(f(".2d", FormatArg::Int(1)), Err(FormatError::InvalidSpec(_))));
+    assert!(matches!(f("+s", FormatArg::Str("a")), Err(FormatError::InvalidSpec(_))));
+    assert!(matches!(f(",x", FormatArg::Int(1)), Err(FormatError::InvalidSpec(_))));
+    assert!(matches!(f("5.f", FormatArg::Float(1.0)), Err(FormatError::InvalidSpec(_))));
+    assert!(matches!(f("abc", FormatArg::Int(1)), Err(FormatError::InvalidSpec(_))));
+}
+
+#[test]
+fn test_float_repr() {
+    assert_eq!(float_repr(0.1), "0.1");
+    assert_eq!(float_repr(1.5e300), "1.5e+300");
+    assert_eq!(float_repr(123456789.0), "123456789.0");
+    assert_eq!(float_repr(1234567890123456.0), "1234567890123456.0");
+    assert_eq!(float_repr(0.00012), "0.00012");
+    assert_eq!(float_repr(-2.5e-7), "-2.5e-07");
+    assert_eq!(float_repr(f64::NEG_INFINITY), "-inf");
+}
+
+#[test]
+fn test_format() {
+    let args = [FormatArg::Int(1), FormatArg::Str("two"), FormatArg::Float(3.5)];
+    let kwargs = [("w", FormatArg::Int(6)), ("q", FormatArg::Str("it's"))];
+    assert_eq!(format("{} {} {}", &args, &[]).unwrap(), "1 two 3.5");
+    assert_eq!(format("{2} {0} {0}", &args, &[]).unwrap(), "3.5 1 1");
+    assert_eq!(format("{{{1}}}", &args, &[]).unwrap(), "{two}");
+    assert_eq!(format("{1:>{w}}|", &args, &kwargs).unwrap(), "   two|");
+    assert_eq!(format("{q!r} {1!r:^7} {1!s}", &args, &kwargs).unwrap(), "\"it's\"  'two'  two");
+    assert_eq!(format("{!a}", &[FormatArg::Str("é\n")], &[]).unwrap(), "'\\xe9\\n'");
+    assert_eq!(format("{} {0}", &args, &[]), Err(FormatError::NumberingSwitch));
+    assert_eq!(format("{5}", &args, &[]), Err(FormatError::MissingArgument(5)));
+    assert_eq!(format("{x}", &args, &[]), Err(FormatError::MissingKeyword(String::from("x"))));
+    assert_eq!(
+        FormatError::MissingKeyword(String::from("x")).to_string(),
+        "no argument named 'x' for the format string"
+    );
+    assert_eq!(format("{0.real}", &args, &[]), Err(FormatError::UnsupportedField(String::from("0.real"))));
+    assert_eq!(format("{0!x}", &args, &[]), Err(FormatError::UnknownConversion('x')));
+    assert!(matches!(format("a } b", &args, &[]), Err(FormatError::Syntax(_))));
+    assert!(matches!(format("a { b", &args, &[]), Err(FormatError::Syntax(_))));
+}
+
+#[test]
+fn test_percent() {
+    let p = |fmt: &str, args: &[FormatArg<'_>]| percent(fmt, args, &[]);
+    assert_eq!(p("%s and %r", &[FormatArg::Str("a"), FormatArg::Str("b")]).unwrap(), "a and 'b'");
+    assert_eq!(p("%5d|%-5d|%05d", &[FormatArg::Int(42), FormatArg::Int(42), FormatArg::Int(-42)]).unwrap(), "   42|42   |-0042");
+    assert_eq!(p("%.3d %x %#X %o", &[FormatArg::Int(5), FormatArg::Int(255), FormatArg::Int(255), FormatArg::Int(8)]).unwrap(), "005 ff 0XFF 10");
+    assert_eq!(p("%d", &[FormatArg::Float(3.7)]).unwrap(), "3");
+    assert_eq!(p("%+.2e %g %G", &[FormatArg::Float(12345.0), FormatArg::Int(1), FormatArg::Float(1e-10)]).unwrap(), "+1.23e+04 1 1E-10");
+    assert_eq!(p("%*.*f", &[FormatArg::Int(8), FormatArg::Int(2), FormatArg::Float(2.34567)]).unwrap(), "    2.35");
+    assert_eq!(p("%c%c", &[FormatArg::Int(72), FormatArg::Char('i')]).unwrap(), "Hi");
+    assert_eq!(p("%5s|%-5s|%.1s", &[FormatArg::Int(1), FormatArg::Bool(true), FormatArg::Str("xyz")]).unwrap(), "    1|True |x");
+    assert_eq!(p("100%%", &[]).unwrap(), "100%");
+    assert_eq!(percent("%(a)s-%(b)05.1f", &[], &[("a", FormatArg::Str("k")), ("b", FormatArg::Float(2.25))]).unwrap(), "k-002.2");
+    assert_eq!(p("%d %d", &[FormatArg::Int(1)]), Err(FormatError::NotEnoughArguments));
+    assert_eq!(p("%d", &[FormatArg::Int(1), FormatArg::Int(2)]), Err(FormatError::TooManyArguments));
+    assert_eq!(p("%d", &[FormatArg::Str("a")]), Err(FormatError::TypeMismatch { code: 'd', type_name: "str" }));
+    assert_eq!(p("%x", &[FormatArg::Float(1.0)]), Err(FormatError::TypeMismatch { code: 'x', type_name: "float" }));
+    assert_eq!(p("%y", &[FormatArg::Int(1)]), Err(FormatError::UnsupportedCharacter { code: 'y', index: 1 }));
+    assert_eq!(p("%5", &[FormatArg::Int(1)]), Err(FormatError::IncompleteFormat));
+    assert_eq!(
+        p("%d", &[FormatArg::Str("a")]).unwrap_err().to_string(),
+        "%d format: a real number is required, not str"
+    );
+}
+
+#[test]
+fn test_format_macros() {
+    let (a, b) = (3, String::from("ab"));
+    assert_eq!(pyformat!("{0} is {name:>5}", a, name=b).unwrap(), "3 is    ab");
+    assert_eq!(pyformat!("{} {}", 'c', 0.5f32).unwrap(), "c 0.5");
+    assert_eq!(pyformat!("plain").unwrap(), "plain");
+    assert_eq!(pypercent!("%5.2f %s", 1.5, &b).unwrap(), " 1.50 ab");
+    assert_eq!(pypercent!("%(x)s=%(y)d", x="k", y=2u8).unwrap(), "k=2");
+    assert_eq!(pyformat!("{:d}", "x").unwrap_err().to_string(), "Unknown format code 'd' for object of type 'str'");
+}
@@ -1,3 +1,5 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 //! # pyprint
 //! 
 //! A Rust library that provides Python-like print functionality with macros.
@@ -8,6 +10,8 @@
 //! - Support for regular, debug, and error printing
 //! - Options for file redirection and flushing
 //! - Helpful macros to reduce boilerplate
+//! - `no_std` support: without the default `std` feature, printing works
+//!   against `core::fmt::Write` destinations using only `alloc`
 //! 
 //! ## Version
 //! 
@@ -16,6 +20,7 @@
 //! ## Examples
 //! 
 //! ```
+//! # #[cfg(feature = "std")] {
 //! use pyprint::pprn;
 //! use pyprint::dprn;
 //! 
@@ -27,79 +32,234 @@
 //! 
 //! // Print with debug formatting
 //! dprn!(vec![1, 2, 3]);  // Prints the vector with default formatting
+//! # }
 //! ```
 
-use std::io::{Write, Result, stdout};
-use std::cell::Cell;
+extern crate alloc;
 
-thread_local! {
-    static LAST_PRINTER_RESULT: Cell<Result<()>> = Cell::new(Ok(()));
-}
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
 
-/// Returns the result of the last print operation.
-/// 
-/// This function is useful for error handling when not using the unwrapping variants
-/// of the print macros.
-/// 
-/// # Returns
-/// 
-/// The `Result` from the last printing operation.
-pub fn last_printer_result() -> Result<()> {
-    let mut res_copy: Cell<Result<()>> = Cell::new(Ok(()));
-    LAST_PRINTER_RESULT.with(|res: &Cell<Result<()>>|{
-        unsafe {
-            std::ptr::copy(res, &mut res_copy, std::mem::size_of::<Cell<Result<()>>>())
-        }
-    });
-    res_copy.into_inner()
-}
+use element::WriteElements;
+#[cfg(feature = "std")]
+use std::io::stdout;
+
+#[cfg(feature = "std")]
+pub mod buffer;
+pub mod config;
+#[cfg(feature = "std")]
+mod defaults;
+pub mod element;
+pub mod error;
+pub mod format;
+pub mod pretty;
+pub mod pystr;
+pub mod repr;
+#[cfg(feature = "std")]
+mod result;
+pub mod sink;
+
+#[cfg(feature = "std")]
+pub use result::{last_printer_result, peek_last_printer_result, take_last_printer_result};
+pub use config::PrintConfig;
+#[cfg(feature = "std")]
+pub use defaults::{set_defaults, with_defaults};
+pub use element::{Element, Spread};
+pub use pystr::PyStr;
+pub use repr::PyRepr;
+pub use sink::{FmtSink, IntoSink, Sink};
+#[cfg(feature = "std")]
+pub use sink::{IoSink, SharedIoSink};
+
+/// The error type reported by print operations: `std::io::Error` with the
+/// `std` feature, `core::fmt::Error` without it.
+#[cfg(feature = "std")]
+pub type Error = std::io::Error;
+
+/// The error type reported by print operations: `std::io::Error` with the
+/// `std` feature, `core::fmt::Error` without it.
+#[cfg(not(feature = "std"))]
+pub type Error = core::fmt::Error;
+
+/// The result type returned by print operations.
+pub type Result<T> = core::result::Result<T, Error>;
 
 /// The main printer struct used by the printing macros.
 /// 
 /// This struct manages the elements to print, formatting options,
-/// and the output destination.
-pub struct Printer {
+/// and the output destination. The lifetime `'a` is that of a borrowed
+/// output destination, so a printer can write into `&mut W` for any `W: Write`.
+/// Destinations implementing `core::fmt::Write`, such as `String` or
+/// `fmt::Formatter`, are accepted as well (see [`sink`]).
+/// 
+/// The macros do not add elements one by one; they pass their arguments to
+/// [`Printer::print_elements`] as `fmt::Arguments` (or spreads of them for
+/// `*iterable`), which are written straight into the locked output without
+/// building intermediate `String`s.
+/// Concurrent prints of up to 8 KiB never interleave mid-line; see
+/// [`Printer::print_args`].
+pub struct Printer<'a> {
     elements: Vec<String>,
-    sep: String,
-    end: String,
-    file: Box<dyn Write>,
-    fls: bool
+    sep: Cow<'a, str>,
+    end: Cow<'a, str>,
+    file: Option<Box<dyn Sink + 'a>>,
+    fls: bool,
+    limit: Option<usize>,
+    #[cfg(feature = "std")]
+    broken_pipe: Option<error::BrokenPipePolicy>,
+}
+
+/// Runs `f` with the default destination: locked stdout with the `std` feature,
+/// or the stdout buffer when [`buffer::buffer_stdout`] is in effect.
+#[cfg(feature = "std")]
+fn with_default_file<R>(f: impl FnOnce(&mut dyn Sink) -> R) -> R {
+    let f = match buffer::with_stdout_buffer(f) {
+        Ok(res) => return res,
+        Err(f) => f,
+    };
+    let out = stdout();
+    let mut lock = IoSink(out.lock());
+    f(&mut lock)
 }
 
-impl Printer {
+/// Runs `f` with the default destination, which rejects all output without
+/// the `std` feature.
+#[cfg(not(feature = "std"))]
+fn with_default_file<R>(f: impl FnOnce(&mut dyn Sink) -> R) -> R {
+    f(&mut sink::Unset)
+}
+
+/// Writes `elements` followed by `args`, separated by `sep` and followed by `end`.
+fn write_elements<A: WriteElements>(
+    file: &mut dyn Sink,
+    elements: &[String],
+    args: &[A],
+    sep: &str,
+    end: &str,
+    fls: bool,
+    limit: Option<usize>,
+) -> Result<()> {
+    let mut first = true;
+    let mut write_one = |a: fmt::Arguments<'_>| {
+        if !first {
+            file.write_str(sep)?;
+        }
+        first = false;
+        file.write_fmt(a)
+    };
+    for s in elements {
+        write_one(format_args!("{s}"))?;
+    }
+    for a in args {
+        a.write_elements(limit, &mut write_one)?;
+    }
+    file.write_str(end)?;
+    file.end_print()?;
+    if fls {
+        file.flush().map_err(error::flush_error)?;
+    }
+    Ok(())
+}
+
+impl Default for Printer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Printer<'a> {
     /// Creates a new Printer with default settings.
     /// 
     /// Default settings:
     /// - separator: space (" ")
     /// - end: newline ("\n")
-    /// - output: stdout
+    /// - output: stdout (without the `std` feature there is no default
+    ///   output and printing fails until a destination is set)
     /// - flush: false
+    /// - limit: none
+    /// 
+    /// Creating a printer does not allocate.
     pub fn new() -> Self {
         Self {
-            elements:Vec::new(), 
-            sep: " ".to_string(), 
-            end: "\n".to_string(), 
-            file: Box::new(stdout()),
-            fls: false
+            elements: Vec::new(),
+            sep: Cow::Borrowed(" "),
+            end: Cow::Borrowed("\n"),
+            file: None,
+            fls: false,
+            limit: None,
+            #[cfg(feature = "std")]
+            broken_pipe: None,
         }
     }
     
+    /// Creates a printer with the defaults of the print macros.
+    /// 
+    #[cfg_attr(
+        feature = "std",
+        doc = "With the `std` feature, these are the options given to \
+               [`set_defaults`] or, within a [`with_defaults`] call, to that call."
+    )]
+    /// Otherwise, and if no defaults were set, this is [`Printer::new`].
+    pub fn from_defaults() -> Self {
+        #[cfg(feature = "std")]
+        if let Some(defaults) = defaults::current() {
+            return defaults.printer();
+        }
+        Self::new()
+    }
+
     /// Adds a string element to be printed.
     pub fn add_element(&mut self, element: String) -> &mut Self {
         self.elements.push(element);
         self
     }
+
+    /// Adds every item of an iterable as a separate element, like `print(*items)`.
+    /// 
+    /// The items are converted to strings up front. To stream a large
+    /// iterator instead, pass it as an [`Element::Spread`] to
+    /// [`Printer::print_elements`].
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use pyprint::Printer;
+    /// 
+    /// let mut printer = Printer::new();
+    /// printer.add_element("items:".to_string()).extend_elements([1, 2, 3]).set_sep(", ");
+    /// assert_eq!(printer.render(), "items:, 1, 2, 3\n");
+    /// ```
+    pub fn extend_elements<I>(&mut self, items: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: fmt::Display,
+    {
+        self.elements.extend(items.into_iter().map(|item| item.to_string()));
+        self
+    }
     
     /// Sets the end string that is printed after all elements.
     /// 
     /// # Example
     /// 
     /// ```
+    /// # #[cfg(feature = "std")] {
     /// use pyprint::pprn;
     /// pprn!("Hello", "World", end="!");  // Prints: Hello World!
+    /// # }
     /// ```
     pub fn set_end(&mut self, end: impl ToString) -> &mut Self {
-        self.end = end.to_string();
+        self.end = Cow::Owned(end.to_string());
+        self
+    }
+
+    /// Sets the end string like [`set_end`](Printer::set_end), borrowing
+    /// it instead of allocating a copy.
+    pub fn set_end_str(&mut self, end: &'a str) -> &mut Self {
+        self.end = Cow::Borrowed(end);
         self
     }
     
@@ -108,11 +268,20 @@ impl Printer {
     /// # Example
     /// 
     /// ```
+    /// # #[cfg(feature = "std")] {
     /// use pyprint::pprn;
     /// pprn!("Hello", "World", sep=", ");  // Prints: Hello, World
+    /// # }
     /// ```
     pub fn set_sep(&mut self, sep: impl ToString) -> &mut Self {
-        self.sep = sep.to_string();
+        self.sep = Cow::Owned(sep.to_string());
+        self
+    }
+
+    /// Sets the separator like [`set_sep`](Printer::set_sep), borrowing it
+    /// instead of allocating a copy.
+    pub fn set_sep_str(&mut self, sep: &'a str) -> &mut Self {
+        self.sep = Cow::Borrowed(sep);
         self
     }
     
@@ -121,14 +290,41 @@ impl Printer {
     /// # Example
     /// 
     /// ```
+    /// # #[cfg(feature = "std")] {
     /// use pyprint::pprint;
     /// use std::fs::File;
     /// 
-    /// let file = File::create("output.txt").unwrap();
-    /// pprint!(file=file, "Hello", "World");  // Writes to output.txt
+    /// let path = std::env::temp_dir().join("pyprint-set-file.txt");
+    /// let file = File::create(&path).unwrap();
+    /// pprint!(file=file, "Hello", "World");  // Writes to the file
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap(), "Hello World\n");
+    /// # }
+    /// ```
+    /// 
+    /// A mutable reference can be passed to keep using the destination afterwards:
+    /// 
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use pyprint::pprn;
+    /// 
+    /// let mut buf = Vec::new();
+    /// pprn!(file=&mut buf, "Hello");
+    /// pprn!(file=&mut buf, "World");
+    /// assert_eq!(buf, b"Hello\nWorld\n");
+    /// # }
+    /// ```
+    /// 
+    /// Anything implementing `core::fmt::Write` works too:
+    /// 
+    /// ```
+    /// use pyprint::pprn;
+    /// 
+    /// let mut s = String::new();
+    /// pprn!(file=&mut s, 1, 2, sep=", ");
+    /// assert_eq!(s, "1, 2\n");
     /// ```
-    pub fn set_file(&mut self, file: impl Write + 'static) -> &mut Self {
-        self.file = Box::new(file);
+    pub fn set_file<M>(&mut self, file: impl IntoSink<'a, M>) -> &mut Self {
+        self.file = Some(Box::new(file.into_sink()));
         self
     }
 
@@ -140,72 +336,400 @@ impl Printer {
     /// # Returns
     /// 
     /// A Result that indicates whether the print operation succeeded.
+    #[cfg_attr(
+        feature = "std",
+        doc = "With the `std` feature, the same outcome is stored for \
+               [`last_printer_result`], and if the output was written but flushing \
+               it failed, the error wraps a `FlushError` (see [`error`])."
+    )]
     pub fn print(&mut self) -> Result<()>{
-        let mut eitr = self.elements.iter();
-        let opt_first = eitr.next();
-        let first = match opt_first {
-            Some(x) => x,
-            None => {write!(self.file, "{}", self.end)?;return Ok(());}
+        self.print_args(&[])
+    }
+
+    /// Executes the print operation with extra elements given as `fmt::Arguments`.
+    /// 
+    /// The arguments are printed after any elements added with
+    /// [`Printer::add_element`] and are formatted directly into the output.
+    /// 
+    /// Each print is atomic with respect to other prints: when printing to
+    /// the default stdout, the stdout lock is held for the whole operation,
+    /// and output for other shared destinations (handles to stdout or stderr,
+    /// and writers wrapped in `sink::SharedIoSink`) is
+    /// assembled and issued as a single `write_all`. Prints longer than 8 KiB
+    /// to such destinations are written in chunks of at most 8 KiB, so only
+    /// shorter prints are guaranteed not to interleave there. Other
+    /// destinations are written to directly.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use pyprint::Printer;
+    /// 
+    /// let mut out = String::new();
+    /// let x = 1.5;
+    /// Printer::new()
+    ///     .set_file(&mut out)
+    ///     .print_args(&[format_args!("x ="), format_args!("{x:.2}")])
+    ///     .unwrap();
+    /// assert_eq!(out, "x = 1.50\n");
+    /// ```
+    pub fn print_args(&mut self, args: &[fmt::Arguments<'_>]) -> Result<()> {
+        self.print_with(args)
+    }
+
+    /// Executes the print operation with extra elements, some of which may
+    /// expand into several elements.
+    /// 
+    /// This is what the print macros call. It behaves like
+    /// [`Printer::print_args`], except that an [`Element::Spread`] writes each
+    /// of its items as a separate element.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use pyprint::{Element, Printer};
+    /// use pyprint::element::Splat;
+    /// 
+    /// let mut out = String::new();
+    /// let items = Splat::new([1, 2, 3], |x, f| f(format_args!("{x}")));
+    /// Printer::new()
+    ///     .set_file(&mut out)
+    ///     .set_sep(", ")
+    ///     .print_elements(&[Element::One(format_args!("items:")), Element::Spread(&items)])
+    ///     .unwrap();
+    /// assert_eq!(out, "items:, 1, 2, 3\n");
+    /// ```
+    pub fn print_elements(&mut self, args: &[Element<'_>]) -> Result<()> {
+        self.print_with(args)
+    }
+
+    fn print_with<A: WriteElements>(&mut self, args: &[A]) -> Result<()> {
+        #[cfg(feature = "std")]
+        let broken_pipe = match (self.broken_pipe, &self.file) {
+            (Some(policy), _) => policy,
+            (None, Some(file)) if !file.is_std_stream() => error::BrokenPipePolicy::Propagate,
+            (None, _) => error::broken_pipe_policy(),
         };
-        write!(self.file, "{}", first)?;
-        for s in eitr {
-            write!(self.file, "{}{}", self.sep, s)?;
-        }
-        write!(self.file, "{}", self.end)?;
-        if self.fls {
-            self.file.flush()?;
-        }
-        Ok(())
+        let Printer { elements, sep, end, file, fls, limit, .. } = self;
+        let res = match file {
+            Some(file) if file.is_shared() => {
+                let mut whole = sink::Assembled::new(&mut **file);
+                write_elements(&mut whole, elements, args, sep, end, *fls, *limit)
+                    .and_then(|()| whole.finish())
+            }
+            Some(file) => write_elements(&mut **file, elements, args, sep, end, *fls, *limit),
+            None => with_default_file(|file| write_elements(file, elements, args, sep, end, *fls, *limit)),
+        };
+        #[cfg(feature = "std")]
+        let res = error::handle_broken_pipe(res, broken_pipe);
+        #[cfg(feature = "std")]
+        result::record_result(&res);
+        res
     }
     
+    /// Returns the text that [`Printer::print`] would write, as a `String`.
+    /// 
+    /// The output destination and flush setting are ignored.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use pyprint::Printer;
+    /// 
+    /// let mut printer = Printer::new();
+    /// printer.add_element("a".to_string()).add_element("b".to_string()).set_sep(", ");
+    /// assert_eq!(printer.render(), "a, b\n");
+    /// ```
+    pub fn render(&self) -> String {
+        self.render_args(&[])
+    }
+
+    /// Returns the text that [`Printer::print_args`] would write, as a `String`.
+    pub fn render_args(&self, args: &[fmt::Arguments<'_>]) -> String {
+        self.render_with(args)
+    }
+
+    /// Returns the text that [`Printer::print_elements`] would write, as a `String`.
+    pub fn render_elements(&self, args: &[Element<'_>]) -> String {
+        self.render_with(args)
+    }
+
+    fn render_with<A: WriteElements>(&self, args: &[A]) -> String {
+        let mut out = String::new();
+        // Writing into a `String` only fails if a `Display` implementation does.
+        let _ = write_elements(
+            &mut FmtSink(&mut out),
+            &self.elements,
+            args,
+            &self.sep,
+            &self.end,
+            false,
+            self.limit,
+        );
+        out
+    }
+
+    /// Writes the elements to the destination, for `write_py!`.
+    /// 
+    /// Unlike [`Printer::print_elements`], this neither applies a broken pipe
+    /// policy nor records the result: `write_py!` runs inside a `Display`
+    /// implementation, and the print that formats it reports the outcome.
+    #[doc(hidden)]
+    pub fn write_py_elements(&mut self, args: &[Element<'_>]) -> Result<()> {
+        let Printer { elements, sep, end, file, fls, limit, .. } = self;
+        match file {
+            Some(file) => write_elements(&mut **file, elements, args, sep, end, *fls, *limit),
+            None => with_default_file(|file| write_elements(file, elements, args, sep, end, *fls, *limit)),
+        }
+    }
+
     /// Sets whether output should be flushed immediately.
     /// 
     /// # Example
     /// 
     /// ```
+    /// # #[cfg(feature = "std")] {
     /// use pyprint::pprn;
     /// pprn!("Progress: ", flush=true);  // Prints and flushes immediately
+    /// # }
     /// ```
     pub fn set_flush(&mut self, fls: bool) -> &mut Self {
         self.fls = fls;
         self
     }
+
+    /// Sets what this print does if its destination is a broken pipe,
+    /// overriding [`error::set_broken_pipe_policy`].
+    /// 
+    /// Only available with the `std` feature.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use pyprint::error::BrokenPipePolicy;
+    /// use pyprint::pprn;
+    /// pprn!("status", broken_pipe=BrokenPipePolicy::Ignore);  // Never panics on a closed pipe
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn set_broken_pipe(&mut self, policy: error::BrokenPipePolicy) -> &mut Self {
+        self.broken_pipe = Some(policy);
+        self
+    }
+
+    /// Limits the number of items printed from each [`Element::Spread`].
+    /// 
+    /// The items after the first `limit` are replaced by a single
+    /// `... N more` element. Iterators are not consumed past the limit and
+    /// one more item, so infinite ones work too; if the number left out is
+    /// not known from the iterator's `size_hint`, the element is just `...`.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use pyprint::pprn;
+    /// pprn!(*0..1_000_000, limit=3, sep=", ");  // Prints: 0, 1, 2, ... 999997 more
+    /// pprn!(*(0..).filter(|x| x % 7 == 0), limit=3);  // Prints: 0 7 14 ...
+    /// # }
+    /// ```
+    pub fn set_limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+// Re-exports used by the macros, so they also work in `no_std` crates
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::format;
+    pub use core::format_args;
+    pub use pyprint_macros::fstring;
 }
 
 // Internal macro implementation details
+//
+// The muncher state is `[mode, finish, [printer expression], [arguments]]`.
+// `mode` is the default format of plain arguments (`display`, `debug`,
+// `pretty`, `pystr` for `PyStr`, `repr` for `PyRepr`, or `named`, which
+// prefixes each value with its expression); it is
+// only applied at the end, so options such as `pretty=true` affect all
+// arguments wherever they appear.
 #[macro_export]
 macro_rules! match_variants {
-    (@process [$fmt:expr, $($processed:tt)*] []) => {
-        $($processed)*.print()
+    // Arguments alone, for `args!`
+    (@process [$mode:ident, args, [], [$($args:tt)*]] []) => {
+        &[$($crate::match_variants!(@element $mode, $args)),*]
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], [$($args:tt)*]] []) => {
+        $($processed)*.$finish(&[$($crate::match_variants!(@element $mode, $args)),*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [sep=$e:literal, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_sep_str(concat!($e))], $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [end=$e:literal, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_end_str(concat!($e))], $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [sep=$e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_sep($e)], $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [end=$e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_end($e)], $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [file=$e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_file($e)], $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [flush=$e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_flush($e)], $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [broken_pipe=$e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_broken_pipe($e)], $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, [$($processed:tt)*], $args:tt] [limit=$e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, [$($processed)*.set_limit($e)], $args] [$($rest)*])
+    };
+
+    (@process [display, $finish:ident, $processed:tt, $args:tt] [pystr=true, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [pystr, $finish, $processed, $args] [$($rest)*])
+    };
+
+    (@process [display, $finish:ident, $processed:tt, $args:tt] [pystr=false, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [display, $finish, $processed, $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, $args:tt] [pystr=$e:expr, $($rest:tt)*]) => {
+        ::core::compile_error!("`pystr=` takes `true` or `false` and is only supported by `pprint!`, `pprn!`, `eprint!`, `eprn!`, `fprint!`, `fprn!`, `sprint!` and `write_py!`")
+    };
+
+    (@process [debug, $finish:ident, $processed:tt, $args:tt] [pretty=true, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [pretty, $finish, $processed, $args] [$($rest)*])
+    };
+
+    (@process [debug, $finish:ident, $processed:tt, $args:tt] [pretty=false, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [debug, $finish, $processed, $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, $args:tt] [pretty=$e:expr, $($rest:tt)*]) => {
+        ::core::compile_error!("`pretty=` takes `true` or `false` and is only supported by `dprint!`, `dprn!`, `deprint!`, `deprn!` and `dsprint!`")
+    };
+
+    (@process [named, $finish:ident, $processed:tt, [$($args:tt)*]] [loc=true, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [named, $finish, $processed, [(@loc) $($args)*]] [$($rest)*])
+    };
+
+    (@process [named, $finish:ident, $processed:tt, $args:tt] [loc=false, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [named, $finish, $processed, $args] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, [$($args:tt)*]] [* $e:expr => $spec:literal, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, $processed, [$($args)* (@splat (::core::concat!("{:", $spec, "}"), $e))]] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, [$($args:tt)*]] [* $e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, $processed, [$($args)* (@splat (@default $e))]] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, [$($args:tt)*]] [? $e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, $processed, [$($args)* ("{:?}", $e)]] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, [$($args:tt)*]] [# ? $e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, $processed, [$($args)* ("{:#?}", $e)]] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, [$($args:tt)*]] [$e:expr => $spec:literal, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, $processed, [$($args)* (::core::concat!("{:", $spec, "}"), $e)]] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, [$($args:tt)*]] [$e:expr, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, $processed, [$($args)* (@default $e)]] [$($rest)*])
+    };
+
+    (@process [$mode:ident, $finish:ident, $processed:tt, $args:tt] [, $($rest:tt)*]) => {
+        $crate::match_variants!(@process [$mode, $finish, $processed, $args] [$($rest)*])
+    };
+
+    // An argument becomes one element, or a spread of the items of `*iterable`
+    (@element named, (@splat (@default $e:expr))) => {
+        $crate::match_variants!(@element debug, (@splat (@default $e)))
+    };
+
+    (@element $mode:ident, (@splat (@default $e:expr))) => {
+        $crate::Element::Spread(&$crate::element::Splat::new($e, |x, f| f($crate::match_variants!(@arg $mode, (@default x)))))
+    };
+
+    (@element $mode:ident, (@splat ($fmt:expr, $e:expr))) => {
+        $crate::Element::Spread(&$crate::element::Splat::new($e, |x, f| f($crate::__private::format_args!($fmt, x))))
+    };
+
+    (@element $mode:ident, $arg:tt) => {
+        $crate::Element::One($crate::match_variants!(@arg $mode, $arg))
+    };
+
+    // Formatting of a single argument
+    (@arg display, (@default $e:expr)) => {
+        $crate::__private::format_args!("{}", $e)
+    };
+
+    (@arg debug, (@default $e:expr)) => {
+        $crate::__private::format_args!("{:?}", $e)
+    };
+
+    (@arg pretty, (@default $e:expr)) => {
+        $crate::__private::format_args!("{:#?}", $e)
+    };
+
+    (@arg pystr, (@default $e:expr)) => {
+        $crate::__private::format_args!("{}", $crate::pystr::Str(&$e))
+    };
+
+    (@arg repr, (@default $e:expr)) => {
+        $crate::__private::format_args!("{}", $crate::repr::Repr(&$e))
+    };
+
+    (@arg named, (@default $e:expr)) => {
+        $crate::__private::format_args!("{}={:?}", ::core::stringify!($e), $e)
     };
 
-    (@process [$fmt:expr, $($processed:tt)*] [sep=$e:expr, $($rest:tt)*]) => {
-        $crate::match_variants!(@process [$fmt, $($processed)*.set_sep($e)] [$($rest)*])
+    (@arg named, (@loc)) => {
+        $crate::__private::format_args!("[{}:{}]", ::core::file!(), ::core::line!())
     };
 
-    (@process [$fmt:expr, $($processed:tt)*] [end=$e:expr, $($rest:tt)*]) => {
-        $crate::match_variants!(@process [$fmt, $($processed)*.set_end($e)] [$($rest)*])
+    (@arg named, ($fmt:expr, $e:expr)) => {
+        $crate::__private::format_args!("{}={}", ::core::stringify!($e), $crate::__private::format_args!($fmt, $e))
     };
 
-    (@process [$fmt:expr, $($processed:tt)*] [file=$e:expr, $($rest:tt)*]) => {
-        $crate::match_variants!(@process [$fmt, $($processed)*.set_file($e)] [$($rest)*])
+    (@arg $mode:ident, ($fmt:expr, $e:expr)) => {
+        $crate::__private::format_args!($fmt, $e)
     };
 
-    (@process [$fmt:expr, $($processed:tt)*] [flush=$e:expr, $($rest:tt)*]) => {
-        $crate::match_variants!(@process [$fmt, $($processed)*.set_flush($e)] [$($rest)*])
+    // Entry point for rendering into a `String`, where `end` defaults to empty
+    (@render $mode:ident, $($t:tt)*) => {
+        $crate::match_variants!(@process [$mode, render_elements, [$crate::Printer::new().set_end("")], []] [$($t)*])
     };
 
-    (@process [$fmt:expr, $($processed:tt)*] [$e:expr, $($rest:tt)*]) => {
-        $crate::match_variants!(@process [$fmt, $($processed)*.add_element(format!($fmt,$e))] [$($rest)*])
+    // Entry point for `write_py!`, which ignores the defaults and where `end`
+    // defaults to empty
+    (@write $mode:ident, $($t:tt)*) => {
+        $crate::match_variants!(@process [$mode, write_py_elements, [$crate::Printer::new().set_end("")], []] [$($t)*])
     };
 
-    (@process [$fmt:expr, $($processed:tt)*] [, $($rest:tt)*]) => {
-        $crate::match_variants!(@process [$fmt, $($processed)*] [$($rest)*])
+    // Entry point for printing with a `PrintConfig`
+    ($mode:ident, @ $cfg:ident, $($t:tt)*) => {
+        $crate::match_variants!(@process [$mode, print_elements, [$cfg.printer()], []] [$($t)*])
     };
 
     // Entry point
-    ($fmt: expr, $($t:tt)*) => {
-        $crate::match_variants!(@process [$fmt, $crate::Printer::new()] [$($t)*])
+    ($mode:ident, $($t:tt)*) => {
+        $crate::match_variants!(@process [$mode, print_elements, [$crate::Printer::from_defaults()], []] [$($t)*])
     };
 }
 
@@ -220,10 +744,41 @@ macro_rules! match_variants {
 /// - `end=VALUE`: Sets the ending string (default: "\n")
 /// - `file=VALUE`: Sets the output destination (default: stdout)
 /// - `flush=BOOL`: Controls whether to flush output immediately
+/// - `broken_pipe=POLICY`: What to do if the destination is a closed pipe, a
+///   `error::BrokenPipePolicy` (`std` only)
+/// - `limit=N`: Prints at most `N` items of each `*iterable`, followed by
+///   `... M more` for the `M` items left out, or `...` if `M` is not known
+///   without consuming them
+/// - `pystr=true`: Renders arguments with [`PyStr`] instead of `Display`, so
+///   `1.0` prints as `1.0`, `None` as `None` and a `Vec` as a Python list
+/// 
+/// Prefixing an argument with `?` prints it with `{:?}` and `#?` with `{:#?}`,
+/// so labels can stay plain while values get Debug formatting.
+/// 
+/// An argument written as `*iterable` is spread into one element per item,
+/// like `print(*iterable)` in Python. The iterable is consumed, so pass
+/// `*&items` or `*items.iter()` to keep using a collection; to print a
+/// dereferenced value, write `(*value)`. A spec as in `*items => "SPEC"`
+/// applies to every item. Items are formatted and written one at a time,
+/// so huge iterators are printed without collecting them first.
+/// 
+/// An argument can carry its own format spec as `value => "SPEC"`, which is
+/// used as `{:SPEC}` instead of the macro's default format. The spec is a
+/// string literal in Rust's format syntax, so width, precision, alignment and
+/// Debug formatting can be mixed freely within one call.
+/// 
+/// Output from concurrent calls never interleaves mid-line: each call of up
+/// to 8 KiB writes its elements, separators and `end` as one unit (see
+/// [`Printer::print_args`]).
+/// 
+/// A [`PrintConfig`] named as the first argument, as in `pprint!(@config, ...)`,
+/// supplies the options; options given in the call override it for that call.
+/// The same works for the other print macros that do not fix the destination.
 /// 
 /// # Examples
 /// 
 /// ```
+/// # #[cfg(feature = "std")] {
 /// use pyprint::pprint;
 /// 
 /// // Basic printing
@@ -232,41 +787,103 @@ macro_rules! match_variants {
 /// // With custom separator and ending
 /// pprint!("Hello", "World", sep=" - ", end="!\n");
 /// 
+/// // Debug formatting for single arguments
+/// let state = Some((1, "a"));
+/// pprint!("state:", ?state);  // Prints: state: Some((1, "a"))
+/// 
+/// // With per-argument format specs
+/// let (name, value, pct) = ("ratio", 0.61803, 42);
+/// pprint!(name, value => ".3", pct => ">6", Some(1) => "?");  // Prints: ratio 0.618     42 Some(1)
+/// 
+/// // Spread the items of a collection, like print(*items, sep=", ")
+/// let items = vec![1, 2, 3];
+/// pprint!("items:", *&items, sep=", ");  // Prints: items:, 1, 2, 3
+/// pprint!(*items.iter().map(|x| x * 10), *["a", "b"]);  // Prints: 10 20 30 a b
+/// 
+/// // Like Python's print(), with Python's str() of each value
+/// pprint!(1.0, None::<i32>, vec![(1, "a")], pystr=true);  // Prints: 1.0 None [(1, 'a')]
+/// 
 /// // Print to a custom output
 /// use std::fs::File;
-/// let file = File::create("output.txt").unwrap();
+/// let file = File::create(std::env::temp_dir().join("pyprint-example.txt")).unwrap();
 /// pprint!(file=file, "Hello", "World");
+/// 
+/// // Reuse a set of options
+/// let mut log = pyprint::PrintConfig::new().sep(" | ").end(" ;\n");
+/// pprint!(@log, "a", "b");  // Prints: a | b ;
+/// pprint!(@log, "c", end="\n");  // Prints: c
+/// # }
+/// ```
+/// 
+/// The Debug, repr and `name=value` macros reject `pystr=` at compile time:
+/// 
+/// ```compile_fail
+/// pyprint::dprint!(1, pystr=true);
 /// ```
 #[macro_export]
 macro_rules! pprint {
     ($($t:tt)*) => {
-        $crate::match_variants!("{}",$($t)*,)
+        $crate::match_variants!(display, $($t)*,)
+    };
+}
+
+/// Builds the argument list of [`Printer::print_elements`] or
+/// [`PrintConfig::print`] from print macro arguments.
+/// 
+/// Arguments are written as for [`pprint!`]: plain values use `Display`, and
+/// `?value`, `#?value`, `value => "SPEC"` and `*iterable` work as usual.
+/// Options such as `sep=` are not accepted. The list borrows temporaries, so
+/// it must be used within the statement that creates it.
+/// 
+/// # Example
+/// 
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use pyprint::{args, PrintConfig};
+/// 
+/// let mut csv = PrintConfig::new().sep(",");
+/// let name = "ratio";
+/// csv.print(args!(name, 0.61803 => ".2", ?'x', *[1, 2])).unwrap();  // Prints: ratio,0.62,'x',1,2
+/// # }
+/// ```
+#[macro_export]
+macro_rules! args {
+    ($($t:tt)*) => {
+        $crate::match_variants!(@process [display, args, [], []] [$($t)*,])
     };
 }
 
 /// Similar to `pprint!`, but unwraps the Result.
 /// 
-/// This is a convenience macro that panics if printing fails.
+/// This is a convenience macro that panics if printing fails. The panic
+/// message names the macro call and the cause, as in
+/// `pprn! at src/main.rs:3: output closed: Broken pipe (os error 32)`.
 /// 
 /// # Examples
 /// 
 /// ```
+/// # #[cfg(feature = "std")] {
 /// use pyprint::pprn;
 /// 
 /// pprn!("Hello", "World", sep=", ");
 /// pprn!(1, 2, 3, end=".\n");
+/// # }
 /// ```
 #[macro_export]
 macro_rules! pprn {
     ($($t:tt)*) => {
-        $crate::pprint!($($t)*).unwrap()
+        $crate::error::unwrap_print(
+            $crate::pprint!($($t)*),
+            $crate::error::Context::new("pprn!", ::core::file!(), ::core::line!()),
+        )
     };
 }
 
 /// Prints values in debug format.
 /// 
 /// This macro uses the `{:?}` formatter, making it suitable for
-/// debugging complex data structures.
+/// debugging complex data structures. With the `pretty=true` option it uses
+/// the multi-line `{:#?}` formatter instead, like `pdprint!`.
 /// 
 /// # Examples
 /// 
@@ -279,11 +896,18 @@ macro_rules! pprn {
 /// let complex = ("tuple", {let mut m = std::collections::HashMap::new(); 
 ///                          m.insert("key", "value"); m});
 /// dprint!(complex);  // Prints debug representation of the tuple
+/// dprint!(complex, pretty=true);  // Prints it over several lines
+/// ```
+/// 
+/// Other macros reject `pretty=` at compile time:
+/// 
+/// ```compile_fail
+/// pyprint::pprint!(1, pretty=true);
 /// ```
 #[macro_export]
 macro_rules! dprint {
     ($($t:tt)*) => {
-        $crate::match_variants!("{:?}", $($t)*,)
+        $crate::match_variants!(debug, $($t)*,)
     };
 }
 
@@ -293,61 +917,866 @@ macro_rules! dprint {
 #[macro_export]
 macro_rules! dprn {
     ($($t:tt)*) => {
-        $crate::dprint!($($t)*).unwrap()
+        $crate::error::unwrap_print(
+            $crate::dprint!($($t)*),
+            $crate::error::Context::new("dprn!", ::core::file!(), ::core::line!()),
+        )
     };
 }
 
-/// Prints to stderr.
+/// Prints values in pretty debug format.
 /// 
-/// Similar to `pprint!` but directs output to standard error.
+/// This macro uses the `{:#?}` formatter, which spreads nested data over
+/// several indented lines. It accepts the same options as `dprint!`.
 /// 
 /// # Examples
 /// 
 /// ```
-/// use pyprint::eprint;
+/// use pyprint::pdprint;
 /// 
-/// eprint!("Error:", "File not found");
+/// pdprint!(vec![(1, "a"), (2, "b")]);  // Prints one tuple field per line
 /// ```
 #[macro_export]
-macro_rules! eprint {
+macro_rules! pdprint {
     ($($t:tt)*) => {
-        $crate::match_variants!("{}", file=std::io::stderr(), $($t)*,)
+        $crate::match_variants!(pretty, $($t)*,)
     };
 }
 
-/// Similar to `eprint!`, but unwraps the Result.
+/// Similar to `pdprint!`, but unwraps the Result.
 /// 
-/// This is a convenience macro for error printing that panics if printing fails.
+/// This is a convenience macro for pretty debug printing that panics if printing fails.
 #[macro_export]
-macro_rules! eprn {
+macro_rules! pdprn {
     ($($t:tt)*) => {
-        $crate::eprint!($($t)*).unwrap()
+        $crate::error::unwrap_print(
+            $crate::pdprint!($($t)*),
+            $crate::error::Context::new("pdprn!", ::core::file!(), ::core::line!()),
+        )
     };
 }
 
-/// Prints to stderr in debug format.
+/// Prints an f-string, like Python's `print(f"...")`.
 /// 
-/// Combines the features of `eprint!` and `dprint!` to output debug format to stderr.
+/// Replacement fields hold Rust expressions, which are evaluated where the
+/// macro is called. A field can end with a conversion, a Python format spec
+/// or both:
+/// 
+/// - `{expr}` and `{expr!s}` use `Display`, `{expr!r}` uses `Debug`.
+/// - `{expr:spec}` formats a number or string with a Python format spec (see
+///   [`format::FormatSpec`]). Specs can contain nested fields, as in `{x:>{width}}`.
+/// - `{expr=}` prints the expression text followed by its `Debug` value, like
+///   `f"{x=}"`. Whitespace around the `=` is kept.
+/// 
+/// Use `{{` and `}}` for literal braces. The f-string may be followed by the
+/// `sep`, `end`, `file` and `flush` options of `pprint!`.
+/// 
+/// Errors in the f-string are reported at compile time, including specs that
+/// are invalid for any value, such as a missing precision or a string spec
+/// with a sign after `!r` or `!s`. A spec that does not suit the type of its
+/// value, such as `{name:d}` for a string, makes the print return an error.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use pyprint::fprint;
+/// 
+/// let (total, v) = (12.5, vec![1, 2, 3]);
+/// fprint!("total={total:.2f} items={v.len()}").unwrap();  // Prints: total=12.50 items=3
+/// fprint!("{v=} {\"a\"!r}").unwrap();  // Prints: v=[1, 2, 3] "a"
+/// 
+/// let (mut out, width) = (String::new(), 8);
+/// fprint!("{total:>{width}}|", file=&mut out).unwrap();
+/// assert_eq!(out, "    12.5|\n");
+/// # }
+/// ```
+/// 
+/// ```compile_fail
+/// let x = 1.5;
+/// pyprint::fprint!("{x:.f}");  // Format specifier missing precision
+/// ```
+/// 
+/// ```compile_fail
+/// let x = 1;
+/// pyprint::fprint!("{x)}");  // Unmatched ')'
+/// ```
 #[macro_export]
-macro_rules! deprint {
-    ($($t:tt)*) => {
-        $crate::match_variants!("{:?}", file=std::io::stderr(), $($t)*,)
+macro_rules! fprint {
+    ($fstr:literal $(, $($t:tt)*)?) => {
+        $crate::pprint!($crate::__private::fstring!($crate, $fstr), $($($t)*)?)
     };
 }
 
-/// Similar to `deprint!`, but unwraps the Result.
+/// Similar to `fprint!`, but unwraps the Result.
 /// 
-/// This is a convenience macro for debug error printing that panics if printing fails.
+/// This is a convenience macro for f-string printing that panics if printing fails.
 #[macro_export]
-macro_rules! deprn {
+macro_rules! fprn {
     ($($t:tt)*) => {
-        $crate::deprint!($($t)*).unwrap()
+        $crate::error::unwrap_print(
+            $crate::fprint!($($t)*),
+            $crate::error::Context::new("fprn!", ::core::file!(), ::core::line!()),
+        )
     };
 }
 
-#[test]
-fn test_print() {
-    pprn!(flush=true,"Hello",34,45,sep=";", end=".\n",34);
-    dprn!(flush=true,"Hello",34,45,sep=";", end=".\n",34);
-    eprn!("Hi!");
+/// Prints values like Python's `print(repr(x))`.
+/// 
+/// Arguments are rendered with [`PyRepr`], so strings are quoted with single
+/// quotes, `Option::None` prints as `None`, booleans as `True`/`False`, and
+/// slices, maps and sets as Python lists, dicts and sets. Accepts the same
+/// options as `pprint!`.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use pyprint::rprint;
+/// 
+/// rprint!("a", Some(1.0), vec![true, false]).unwrap();  // Prints: 'a' 1.0 [True, False]
+/// rprint!((1,), None::<i32>, sep=", ").unwrap();  // Prints: (1,), None
+/// # }
+/// ```
+#[macro_export]
+macro_rules! rprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(repr, $($t)*,)
+    };
+}
+
+/// Similar to `rprint!`, but unwraps the Result.
+/// 
+/// This is a convenience macro for repr printing that panics if printing fails.
+#[macro_export]
+macro_rules! rprn {
+    ($($t:tt)*) => {
+        $crate::error::unwrap_print(
+            $crate::rprint!($($t)*),
+            $crate::error::Context::new("rprn!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints each argument as `expression=value`, like Python's `f"{x=}"`.
+/// 
+/// Values use the `{:?}` formatter; arguments marked with `?`, `#?` or
+/// `=> "SPEC"` keep their own format after the `=`. With the `loc=true`
+/// option the output starts with `[file:line]` of the call. The other options
+/// of `pprint!` apply as usual.
+/// 
+/// # Examples
+/// 
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use pyprint::vprint;
+/// 
+/// let (x, y) = (5, vec![1, 2]);
+/// vprint!(x, y).unwrap();  // Prints: x=5 y=[1, 2]
+/// vprint!(x + 1, y.len() => "03", sep=", ").unwrap();  // Prints: x + 1=6, y.len()=002
+/// vprint!(x, loc=true).unwrap();  // Prints: [src/main.rs:7] x=5
+/// # }
+/// ```
+#[macro_export]
+macro_rules! vprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(named, $($t)*,)
+    };
+}
+
+/// Similar to `vprint!`, but unwraps the Result.
+/// 
+/// This is a convenience macro for self-documenting printing that panics if printing fails.
+#[macro_export]
+macro_rules! vprn {
+    ($($t:tt)*) => {
+        $crate::error::unwrap_print(
+            $crate::vprint!($($t)*),
+            $crate::error::Context::new("vprn!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr.
+/// 
+/// Only available with the `std` feature.
+/// 
+/// Similar to `pprint!` but directs output to standard error.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use pyprint::eprint;
+/// 
+/// eprint!("Error:", "File not found");
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! eprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(display, file=std::io::stderr(), $($t)*,)
+    };
+}
+
+/// Similar to `eprint!`, but unwraps the Result.
+/// 
+/// This is a convenience macro for error printing that panics if printing fails.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! eprn {
+    ($($t:tt)*) => {
+        $crate::error::unwrap_print(
+            $crate::eprint!($($t)*),
+            $crate::error::Context::new("eprn!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr in debug format.
+/// 
+/// Combines the features of `eprint!` and `dprint!` to output debug format to stderr.
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! deprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(debug, file=std::io::stderr(), $($t)*,)
+    };
+}
+
+/// Similar to `deprint!`, but unwraps the Result.
+/// 
+/// This is a convenience macro for debug error printing that panics if printing fails.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! deprn {
+    ($($t:tt)*) => {
+        $crate::error::unwrap_print(
+            $crate::deprint!($($t)*),
+            $crate::error::Context::new("deprn!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr in pretty debug format.
+/// 
+/// Combines the features of `eprint!` and `pdprint!`.
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! pdeprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(pretty, file=std::io::stderr(), $($t)*,)
+    };
+}
+
+/// Similar to `pdeprint!`, but unwraps the Result.
+/// 
+/// This is a convenience macro for pretty debug error printing that panics if printing fails.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! pdeprn {
+    ($($t:tt)*) => {
+        $crate::error::unwrap_print(
+            $crate::pdeprint!($($t)*),
+            $crate::error::Context::new("pdeprn!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr as `expression=value`.
+/// 
+/// Combines the features of `eprint!` and `vprint!`.
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! evprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(named, file=std::io::stderr(), $($t)*,)
+    };
+}
+
+/// Similar to `evprint!`, but unwraps the Result.
+/// 
+/// This is a convenience macro for self-documenting error printing that panics if printing fails.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! evprn {
+    ($($t:tt)*) => {
+        $crate::error::unwrap_print(
+            $crate::evprint!($($t)*),
+            $crate::error::Context::new("evprn!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints like [`pprint!`], but returns a [`PrintError`](error::PrintError) that
+/// records the cause and the macro call.
+/// 
+/// `PrintError` implements `std::error::Error`, so `?` works in functions
+/// returning `Box<dyn Error>` or similar error types.
+/// 
+/// # Example
+/// 
+/// ```
+/// use pyprint::error::PrintError;
+/// use pyprint::try_pprint;
+/// 
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     try_pprint!("Hello", "World")?;
+///     // A destination that has no room left counts as closed
+///     let mut full = [0u8; 4];
+///     let err = try_pprint!(file=&mut full[..], "too long").unwrap_err();
+///     assert!(matches!(err, PrintError::Closed(..)));
+///     Ok(())
+/// }
+/// ```
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_pprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::pprint!($($t)*),
+            $crate::error::Context::new("try_pprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints like [`dprint!`], but returns a [`PrintError`](error::PrintError) that
+/// records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_dprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::dprint!($($t)*),
+            $crate::error::Context::new("try_dprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints like [`pdprint!`], but returns a [`PrintError`](error::PrintError) that
+/// records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_pdprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::pdprint!($($t)*),
+            $crate::error::Context::new("try_pdprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints like [`fprint!`], but returns a [`PrintError`](error::PrintError) that
+/// records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_fprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::fprint!($($t)*),
+            $crate::error::Context::new("try_fprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints like [`rprint!`], but returns a [`PrintError`](error::PrintError) that
+/// records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_rprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::rprint!($($t)*),
+            $crate::error::Context::new("try_rprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints like [`vprint!`], but returns a [`PrintError`](error::PrintError) that
+/// records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_vprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::vprint!($($t)*),
+            $crate::error::Context::new("try_vprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr like [`eprint!`], but returns a [`PrintError`](error::PrintError)
+/// that records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_eprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::eprint!($($t)*),
+            $crate::error::Context::new("try_eprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr like [`deprint!`], but returns a [`PrintError`](error::PrintError)
+/// that records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_deprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::deprint!($($t)*),
+            $crate::error::Context::new("try_deprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr like [`pdeprint!`], but returns a [`PrintError`](error::PrintError)
+/// that records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_pdeprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::pdeprint!($($t)*),
+            $crate::error::Context::new("try_pdeprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Prints to stderr like [`evprint!`], but returns a [`PrintError`](error::PrintError)
+/// that records the cause and the macro call.
+/// 
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! try_evprint {
+    ($($t:tt)*) => {
+        $crate::error::with_context(
+            $crate::evprint!($($t)*),
+            $crate::error::Context::new("try_evprint!", ::core::file!(), ::core::line!()),
+        )
+    };
+}
+
+/// Returns the text `pprint!` would produce as a `String`.
+/// 
+/// Accepts the same options as `pprint!`, but `end` defaults to an empty
+/// string and `file`/`flush` have no effect.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use pyprint::sprint;
+/// 
+/// assert_eq!(sprint!("Hello", "World"), "Hello World");
+/// assert_eq!(sprint!(1, 2, 3, sep=", ", end="."), "1, 2, 3.");
+/// ```
+#[macro_export]
+macro_rules! sprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(@render display, $($t)*,)
+    };
+}
+
+/// Returns the text `dprint!` would produce as a `String`.
+/// 
+/// Like `sprint!`, but uses the `{:?}` formatter.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use pyprint::dsprint;
+/// 
+/// assert_eq!(dsprint!("a", vec![1, 2]), "\"a\" [1, 2]");
+/// ```
+#[macro_export]
+macro_rules! dsprint {
+    ($($t:tt)*) => {
+        $crate::match_variants!(@render debug, $($t)*,)
+    };
+}
+
+/// Writes values Python-style into a `core::fmt::Write` destination.
+/// 
+/// This is the counterpart of `write!` for use inside `Display` implementations:
+/// it accepts the options of `pprint!` and returns a `fmt::Result`. As with
+/// `write!`, `end` defaults to an empty string, and the defaults set with
+/// `set_defaults` or `with_defaults` do not apply.
+/// 
+/// # Examples
+/// 
+/// ```
+/// use pyprint::write_py;
+/// use std::fmt;
+/// 
+/// struct Point(i32, i32);
+/// 
+/// impl fmt::Display for Point {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write_py!(f, self.0, self.1, sep=", ")
+///     }
+/// }
+/// 
+/// assert_eq!(format!("({})", Point(1, 2)), "(1, 2)");
+/// ```
+#[macro_export]
+macro_rules! write_py {
+    ($dst:expr, $($t:tt)*) => {
+        $crate::match_variants!(@write display, file=$crate::FmtSink(&mut *$dst), $($t)*,)
+            .map_err(|_| ::core::fmt::Error)
+    };
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_print() {
+    pprn!(flush=true,"Hello",34,45,sep=";", end=".\n",34);
+    dprn!(flush=true,"Hello",34,45,sep=";", end=".\n",34);
+    eprn!("Hi!");
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+struct FailingWriter;
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+impl std::io::Write for FailingWriter {
+    fn write(&mut self, _buf: &[u8]) -> Result<usize> {
+        Err(std::io::Error::other("injected failure"))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_last_printer_result() {
+    use std::io::ErrorKind;
+
+    assert!(pprint!(file=FailingWriter, "Hello").is_err());
+    assert_eq!(peek_last_printer_result(), Err(ErrorKind::Other));
+    assert_eq!(last_printer_result().unwrap_err().to_string(), "injected failure");
+    let err = take_last_printer_result().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+    assert_eq!(err.to_string(), "injected failure");
+    assert!(take_last_printer_result().is_ok());
+
+    assert!(std::panic::catch_unwind(|| deprn!(file=FailingWriter, "Hello")).is_err());
+    assert_eq!(peek_last_printer_result(), Err(ErrorKind::Other));
+    pprn!(file=Vec::new(), "Hello");
+    assert_eq!(peek_last_printer_result(), Ok(()));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_borrowed_file() {
+    let mut buf = Vec::new();
+    for i in 0..3 {
+        pprn!(file=&mut buf, "line", i, sep="=");
+    }
+    dprn!(file=&mut buf, "end", end="");
+    assert_eq!(String::from_utf8(buf).unwrap(), "line=0\nline=1\nline=2\n\"end\"");
+
+    let out = stdout();
+    let mut lock = out.lock();
+    pprn!(file=&mut lock, "Hello", end="");
+    pprn!(file=&mut lock, "", "World");
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(sprint!(), "");
+    assert_eq!(sprint!("Hello", 34, sep=";", end=".\n", 45), "Hello;34;45.\n");
+    assert_eq!(dsprint!("Hello", Some(1), sep=", "), "\"Hello\", Some(1)");
+    let mut printer = Printer::new();
+    printer.add_element(String::from("x"));
+    assert_eq!(printer.render(), "x\n");
+}
+
+#[test]
+fn test_fmt_file() {
+    use core::fmt;
+
+    struct Pair(i32, &'static str);
+
+    impl fmt::Display for Pair {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write_py!(f, self.0, self.1, sep=": ")
+        }
+    }
+
+    let mut s = String::new();
+    pprn!(file=&mut s, "a", 1, sep=",");
+    dprn!(file=&mut s, "b", end="");
+    assert_eq!(s, "a,1\n\"b\"");
+    assert_eq!(alloc::format!("{}", Pair(3, "x")), "3: x");
+    assert_eq!(alloc::format!("[{}]", Pair(4, "y")), "[4: y]");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_concurrent_prints_do_not_interleave() {
+    use std::sync::{Arc, Mutex};
+
+    /// A shared buffer that takes its lock separately for every write call,
+    /// passed as a `SharedIoSink`.
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    let shared = Shared(Arc::new(Mutex::new(Vec::new())));
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let out = shared.clone();
+            std::thread::spawn(move || {
+                for i in 0..200 {
+                    pprn!(file=sink::SharedIoSink(out.clone()), "thread", t, "line", i, "a", "b", "c", sep="|");
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    let text = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(text.lines().count(), 8 * 200);
+    for line in text.lines() {
+        let fields: Vec<_> = line.split('|').collect();
+        assert_eq!(fields.len(), 7, "interleaved line: {line:?}");
+        assert_eq!((fields[0], fields[2], &fields[4..]), ("thread", "line", &["a", "b", "c"][..]));
+    }
+}
+
+#[test]
+fn test_format_specs() {
+    let (name, value, pct) = ("x", 1.23456, 42);
+    assert_eq!(sprint!(name, value => ".3", pct => ">6"), "x 1.235     42");
+    assert_eq!(sprint!("x", 1.5 => "<6.2", "|", sep=""), "x1.50  |");
+    assert_eq!(sprint!(Some("a") => "?", [1, 2] => "?", 255 => "#x"), "Some(\"a\") [1, 2] 0xff");
+    assert_eq!(dsprint!("a", "b" => "", 7 => "03"), "\"a\" b 007");
+    assert_eq!(sprint!(Some(1) => "#?", end="!"), "Some(\n    1,\n)!");
+}
+
+#[test]
+fn test_debug_markers() {
+    let state = Some((1, "a"));
+    assert_eq!(sprint!("state:", ?state), "state: Some((1, \"a\"))");
+    assert_eq!(sprint!("s", ?"s", #?Some(2), sep="|"), "s|\"s\"|Some(\n    2,\n)");
+    assert_eq!(dsprint!("quoted", ?"also quoted"), "\"quoted\" \"also quoted\"");
+    let mut buf = String::new();
+    pprn!(file=&mut buf, "v", ?[1, 2], end="");
+    assert_eq!(buf, "v [1, 2]");
+}
+
+#[test]
+fn test_pretty_debug() {
+    let pair = (1, "a");
+    let mut s = String::new();
+    pdprn!(file=&mut s, pair, end="");
+    assert_eq!(s, "(\n    1,\n    \"a\",\n)");
+    let mut t = String::new();
+    dprn!(file=&mut t, pair, pretty=true, end="");
+    assert_eq!(s, t);
+    assert_eq!(dsprint!(Some(1), "x", pretty=true, sep=" "), "Some(\n    1,\n) \"x\"");
+    assert_eq!(dsprint!(Some(1), pretty=false), "Some(1)");
+}
+
+#[test]
+fn test_fprint() {
+    let (total, v, name, width) = (12.5, [1, 2, 3], "ab", 6);
+    let mut s = String::new();
+    fprn!("total={total:.2f} items={v.len()}", file=&mut s);
+    fprn!("{name!r} {name!s:>4} {v=} { width = } {name=:*^{width}}", file=&mut s, end="");
+    assert_eq!(s, "total=12.50 items=3\n\"ab\"   ab v=[1, 2, 3]  width = 6 name=**ab**");
+    let mut t = String::new();
+    fprn!("{{{v[0] + v[1]}}} {v!r:>12} {1.5f64:%}", file=&mut t, end="!");
+    assert_eq!(t, "{3}    [1, 2, 3] 150.000000%!");
+    let mut u = String::new();
+    assert!(fprint!("{name:d}", file=&mut u).is_err());
+    let mut w = String::new();
+    fprn!("{alloc::vec![1, 2].len()} {name.contains('}')} {name.split(':').count():>3} {['!', ':'].len()=}", file=&mut w, end="");
+    assert_eq!(w, "2 false   1 ['!', ':'].len()=2");
+}
+
+#[test]
+fn test_named() {
+    let (x, y, name) = (5, [1, 2], "ab");
+    let mut s = String::new();
+    vprn!(file=&mut s, x, y, name);
+    vprn!(x + 1, y.len() => "03", ?name, sep=", ", end=".\n", file=&mut s);
+    assert_eq!(s, "x=5 y=[1, 2] name=\"ab\"\nx + 1=6, y.len()=002, name=\"ab\".\n");
+    let mut t = String::new();
+    vprn!(x, file=&mut t, loc=true, end="");
+    assert_eq!(t, alloc::format!("[{}:{}] x=5", file!(), line!() - 1));
+}
+
+#[test]
+fn test_repr_print() {
+    let mut s = String::new();
+    rprn!("a", Some(1.0), [true, false], ?"b", file=&mut s);
+    rprn!((1,), None::<i32>, 3 => "03", sep=", ", end="", file=&mut s);
+    assert_eq!(s, "'a' 1.0 [True, False] \"b\"\n(1,), None, 003");
+}
+
+#[test]
+fn test_pystr_mode() {
+    let mut s = String::new();
+    pprn!(1.0, None::<i32>, [(1, "a")], "b", pystr=true, ?"c", file=&mut s);
+    assert_eq!(s, "1.0 None [(1, 'a')] b \"c\"\n");
+    assert_eq!(sprint!(pystr=true, Some(2.0), 1e16), "2.0 1e+16");
+    assert_eq!(sprint!(pystr=false, 2.0), "2");
+}
+
+#[test]
+fn test_splat() {
+    let items = Vec::from([1, 2, 3]);
+    let mut s = String::new();
+    pprn!("items:", *&items, sep=", ", file=&mut s);
+    pprn!(*items.iter().map(|x| x * 10), "|", *&items => "02", *[0; 0], file=&mut s);
+    dprn!(*["a"], *&items, 'b', file=&mut s);
+    rprn!(*[Some("x"), None], file=&mut s);
+    vprn!(*[1, 2], file=&mut s);
+    pprn!(*[0; 0], file=&mut s);
+    assert_eq!(s, "items:, 1, 2, 3\n10 20 30 | 01 02 03\n\"a\" 1 2 3 'b'\n'x' None\n1 2\n\n");
+    assert_eq!(sprint!(*items, sep="-"), "1-2-3");
+    let mut printer = Printer::new();
+    printer.extend_elements(["x", "y"]).set_sep("+");
+    assert_eq!(printer.render_args(&[format_args!("z")]), "x+y+z\n");
+}
+
+#[test]
+fn test_limit() {
+    let mut s = String::new();
+    pprn!(*0..1_000_000, limit=3, sep=", ", file=&mut s);
+    pprn!("a", *[1, 2], *0..5 => "02", "b", limit=2, file=&mut s);
+    rprn!(*["x", "y"], limit=0, file=&mut s);
+    pprn!(*(1..).take(4), limit=4, file=&mut s);
+    pprn!(*(1..).map(|x| x * x), limit=2, file=&mut s);
+    assert_eq!(s, "0, 1, 2, ... 999997 more\na 1 2 00 01 ... 3 more b\n... 2 more\n1 2 3 4\n1 4 ...\n");
+    assert_eq!(sprint!(*"abc".chars(), limit=1), "a ...");
+    assert_eq!(sprint!(*"abc".chars().filter(|_| true), limit=3), "a b c");
+
+    // Iteration stops after the limit and one more item
+    let consumed = core::cell::Cell::new(0);
+    sprint!(*(0..10).inspect(|_| consumed.set(consumed.get() + 1)).filter(|_| true), limit=2);
+    assert_eq!(consumed.get(), 3);
+}
+
+#[test]
+fn test_sep_end_values() {
+    let mut s = String::new();
+    pprn!("a", "b", sep='-', end='\n', file=&mut s);
+    let sep = String::from(", ");
+    pprn!(1, 2, sep=&sep, end=1, file=&mut s);
+    pprn!(3, 4, sep=sep, end=String::from(";\n"), file=&mut s);
+    assert_eq!(s, "a-b\n1, 213, 4;\n");
+    let mut printer = Printer::new();
+    printer.extend_elements([1, 2]).set_sep('|').set_end_str("");
+    assert_eq!(printer.render(), "1|2");
+}
+
+#[test]
+#[should_panic(expected = "can only be written once")]
+fn test_splat_reuse() {
+    let items = element::Splat::new([1, 2], |x, f| f(format_args!("{x}")));
+    let args = [Element::Spread(&items)];
+    assert_eq!(Printer::new().render_elements(&args), "1 2\n");
+    Printer::new().render_elements(&args);
+}
+
+#[cfg(test)]
+struct FlushCounter {
+    out: String,
+    flushes: usize,
+    fail_write: bool,
+    fail_flush: bool,
+}
+
+#[cfg(test)]
+impl FlushCounter {
+    fn new() -> Self {
+        FlushCounter { out: String::new(), flushes: 0, fail_write: false, fail_flush: false }
+    }
+}
+
+#[cfg(test)]
+impl Sink for FlushCounter {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        if self.fail_write {
+            return Err(sink::fmt_error());
+        }
+        self.out.push_str(s);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.fail_flush {
+            return Err(sink::fmt_error());
+        }
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_flush_empty() {
+    let mut file = FlushCounter::new();
+    pprn!(end="\r", flush=true, file=&mut file);
+    pprn!(flush=true, file=&mut file);
+    pprn!(file=&mut file);
+    Printer::new().set_flush(true).set_file(&mut file).print().unwrap();
+    dprn!(*[0; 0], flush=true, file=&mut file);
+    assert_eq!(file.out, "\r\n\n\n\n");
+    assert_eq!(file.flushes, 4);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_flush_error() {
+    let mut file = FlushCounter::new();
+    file.fail_flush = true;
+    assert!(pprint!(file=&mut file).is_ok());
+    let err = pprint!(flush=true, file=&mut file).unwrap_err();
+    assert!(error::is_flush_error(&err));
+    let err = pprint!(1, 2, flush=true, file=&mut file).unwrap_err();
+    assert!(error::is_flush_error(&err));
+    assert_eq!(file.out, "\n\n1 2\n");
+    file.fail_write = true;
+    let err = pprint!(1, flush=true, file=&mut file).unwrap_err();
+    assert!(!error::is_flush_error(&err));
 }
@@ -0,0 +1,774 @@
+//! A port of Python's `pprint` module.
+//!
+//! [`PrettyPrinter`] writes values like [`repr()`](crate::repr) when they fit
+//! in the configured width, and otherwise breaks lists, tuples, sets and dicts
+//! over several indented lines with the layout of Python's `pprint.pformat`.
+//! The output differs from Python's in two ways: long strings are never split
+//! into several literals across lines, and dict keys are sorted with numbers
+//! by value but strings and other keys by the text of their repr, so keys with
+//! escapes or mixed quotes may come out in a different order.
+//! Values describe their structure through [`PyRepr::py_tree`]; the
+//! [`pp!`](crate::pp) macro prints a value with a one-off printer.
+//!
+//! Like Python's `reprlib`, a printer can also limit how much of a value it
+//! shows: nesting beyond `depth` is written as `[...]`, collections longer than
+//! `max_items` end with `... N more`, and long strings are cut in the middle.
+//! Cycles through `Rc` and `Arc` pointers are written as `[...]` instead of
+//! recursing forever, so [`saferepr`] and [`pformat`] are safe to use on
+//! graph-like structures.
+//!
+//! ```
+//! use pyprint::pretty::PrettyPrinter;
+//!
+//! let v = vec![vec![1, 2, 3], vec![4, 5, 6]];
+//! assert_eq!(PrettyPrinter::new().set_width(10).pformat(&v), "[[1,\n  2,\n  3],\n [4,\n  5,\n  6]]");
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::mem;
+
+use crate::repr::{repr, PyRepr};
+
+/// The kind of a Python container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A list, written as `[a, b]`.
+    List,
+    /// A tuple, written as `(a, b)` or `(a,)`.
+    Tuple,
+    /// A set, written as `{a, b}` or `set()`.
+    Set,
+    /// A dict, written as `{k: v}`. Its items are added with [`Tree::entry`].
+    Dict,
+}
+
+/// A node of the structure built by [`Tree`].
+#[derive(Debug)]
+enum Node {
+    /// A value written as a whole.
+    Atom(String),
+    /// A container and its items.
+    Seq(Kind, Vec<Node>),
+    /// A dict entry.
+    Pair(Box<Node>, Box<Node>),
+}
+
+impl Node {
+    /// Writes the node on a single line.
+    fn write_flat(&self, out: &mut String) {
+        match self {
+            Node::Atom(s) => out.push_str(s),
+            Node::Pair(key, value) => {
+                key.write_flat(out);
+                out.push_str(": ");
+                value.write_flat(out);
+            }
+            Node::Seq(Kind::Set, items) if items.is_empty() => out.push_str("set()"),
+            Node::Seq(kind, items) => {
+                let (open, close) = brackets(*kind);
+                out.push_str(open);
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    item.write_flat(out);
+                }
+                if *kind == Kind::Tuple && items.len() == 1 {
+                    out.push(',');
+                }
+                out.push_str(close);
+            }
+        }
+    }
+
+    fn flat(&self) -> String {
+        let mut out = String::new();
+        self.write_flat(&mut out);
+        out
+    }
+}
+
+fn brackets(kind: Kind) -> (&'static str, &'static str) {
+    match kind {
+        Kind::List => ("[", "]"),
+        Kind::Tuple => ("(", ")"),
+        Kind::Set | Kind::Dict => ("{", "}"),
+    }
+}
+
+/// Orders dict keys like Python's `sorted()` would for common key types:
+/// numbers by value, strings and other atoms by text, tuples item by item.
+fn compare_keys(a: &Node, b: &Node) -> Ordering {
+    match (a, b) {
+        (Node::Atom(x), Node::Atom(y)) => match (x.parse::<f64>(), y.parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            _ => x.cmp(y),
+        },
+        (Node::Seq(_, x), Node::Seq(_, y)) => x
+            .iter()
+            .zip(y)
+            .map(|(a, b)| compare_keys(a, b))
+            .find(|o| o.is_ne())
+            .unwrap_or(x.len().cmp(&y.len())),
+        _ => a.flat().cmp(&b.flat()),
+    }
+}
+
+/// Returns `items` sorted by `key` in the order of [`compare_keys`], for the
+/// repr of collections such as `HashMap` that have no order of their own.
+#[cfg(feature = "std")]
+pub(crate) fn sorted<'a, T, K: PyRepr + ?Sized + 'a>(
+    items: impl Iterator<Item = T>,
+    key: impl Fn(&T) -> &'a K,
+) -> Vec<T> {
+    let printer = PrettyPrinter::new();
+    let mut keyed: Vec<_> = items
+        .map(|item| {
+            let mut tree = Tree::new(&printer);
+            key(&item).py_tree(&mut tree);
+            (tree.pop(), item)
+        })
+        .collect();
+    keyed.sort_by(|a, b| compare_keys(&a.0, &b.0));
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Collects the structure of a value for the [`PrettyPrinter`].
+///
+/// [`PyRepr::py_tree`] implementations add exactly one node: an atom for
+/// values that are written as a whole, or a container whose items are added
+/// in the closure passed to [`Tree::seq`].
+///
+/// # Example
+///
+/// ```
+/// use pyprint::pretty::{Kind, Tree};
+/// use pyprint::repr::PyRepr;
+/// use std::fmt;
+///
+/// struct Pair(i32, String);
+///
+/// impl PyRepr for Pair {
+///     fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "({}, {})", self.0.repr(), self.1.repr())
+///     }
+///
+///     fn py_tree(&self, tree: &mut Tree) {
+///         tree.seq(Kind::Tuple, |t| {
+///             t.item(&self.0);
+///             t.item(&self.1);
+///         });
+///     }
+/// }
+/// ```
+pub struct Tree {
+    nodes: Vec<Node>,
+    /// Number of items left out of the current container.
+    skipped: usize,
+    level: usize,
+    /// Addresses of the shared values being visited, to detect cycles.
+    shared: Vec<*const ()>,
+    depth: Option<usize>,
+    max_items: Option<usize>,
+    max_string: Option<usize>,
+    sort_dicts: bool,
+}
+
+impl Tree {
+    fn new(printer: &PrettyPrinter) -> Self {
+        Tree {
+            nodes: Vec::new(),
+            skipped: 0,
+            level: 0,
+            shared: Vec::new(),
+            depth: printer.depth,
+            max_items: printer.max_items,
+            max_string: printer.max_string,
+            sort_dicts: printer.sort_dicts,
+        }
+    }
+
+    /// Adds a value that is written as a whole with [`PyRepr::fmt_repr`].
+    ///
+    /// A representation longer than the printer's `max_string` is cut in the
+    /// middle, as in `'abc...xyz'`.
+    pub fn atom<T: PyRepr + ?Sized>(&mut self, value: &T) {
+        let mut text = repr(value);
+        if let Some(max) = self.max_string {
+            let len = text.chars().count();
+            if len > max {
+                let head = (max - 3) / 2;
+                let tail = max - 3 - head;
+                let mut chars = text.chars();
+                let start: String = chars.by_ref().take(head).collect();
+                let end: String = chars.skip(len - head - tail).collect();
+                text = alloc::format!("{start}...{end}");
+            }
+        }
+        self.nodes.push(Node::Atom(text));
+    }
+
+    /// Adds a container whose items are added by `items`.
+    ///
+    /// Beyond the printer's depth limit the container is written as `[...]`
+    /// and `items` is not called.
+    pub fn seq(&mut self, kind: Kind, items: impl FnOnce(&mut Tree)) {
+        if self.depth.is_some_and(|depth| self.level >= depth) {
+            let (open, close) = brackets(kind);
+            self.nodes.push(Node::Atom(alloc::format!("{open}...{close}")));
+            return;
+        }
+        let outer = mem::take(&mut self.nodes);
+        let outer_skipped = mem::take(&mut self.skipped);
+        self.level += 1;
+        items(self);
+        self.level -= 1;
+        let mut nodes = mem::replace(&mut self.nodes, outer);
+        let skipped = mem::replace(&mut self.skipped, outer_skipped);
+        if kind == Kind::Dict && self.sort_dicts {
+            nodes.sort_by(|a, b| match (a, b) {
+                (Node::Pair(a, _), Node::Pair(b, _)) => compare_keys(a, b),
+                _ => Ordering::Equal,
+            });
+        }
+        if skipped > 0 {
+            nodes.push(Node::Atom(alloc::format!("... {skipped} more")));
+        }
+        self.nodes.push(Node::Seq(kind, nodes));
+    }
+
+    /// Adds an item to a list, tuple or set.
+    ///
+    /// Items beyond the printer's `max_items` are only counted.
+    pub fn item<T: PyRepr + ?Sized>(&mut self, value: &T) {
+        if self.is_full() {
+            self.skipped += 1;
+            return;
+        }
+        value.py_tree(self);
+    }
+
+    /// Adds an entry to a dict.
+    ///
+    /// Entries beyond the printer's `max_items` are only counted.
+    pub fn entry<K: PyRepr + ?Sized, V: PyRepr + ?Sized>(&mut self, key: &K, value: &V) {
+        if self.is_full() {
+            self.skipped += 1;
+            return;
+        }
+        key.py_tree(self);
+        value.py_tree(self);
+        let value = self.pop();
+        let key = self.pop();
+        self.nodes.push(Node::Pair(Box::new(key), Box::new(value)));
+    }
+
+    /// Adds a value reached through a shared pointer such as `Rc` or `Arc`.
+    ///
+    /// If the value is already being visited further up, the structure is
+    /// cyclic and the value is written as its brackets around `...`, as in
+    /// Python's `[[...]]`.
+    pub fn shared<T: PyRepr + ?Sized>(&mut self, value: &T) {
+        let address = value as *const T as *const ();
+        if self.shared.contains(&address) {
+            // Written like a container beyond the depth limit
+            let depth = self.depth.replace(self.level);
+            value.py_tree(self);
+            self.depth = depth;
+            return;
+        }
+        self.shared.push(address);
+        value.py_tree(self);
+        self.shared.pop();
+    }
+
+    fn is_full(&self) -> bool {
+        self.max_items.is_some_and(|max| self.nodes.len() >= max)
+    }
+
+    fn pop(&mut self) -> Node {
+        self.nodes.pop().unwrap_or(Node::Atom(String::new()))
+    }
+}
+
+/// Formats values like Python's `pprint.PrettyPrinter`.
+///
+/// The options match Python's: `width` (default 80) is the desired maximum
+/// line width, `indent` (default 1) the indentation added per nesting level,
+/// `depth` the number of nesting levels shown before containers are written as
+/// `[...]`, `compact` packs as many list items on each line as fit, and
+/// `sort_dicts` (default true) sorts dict entries by key. Following Python's
+/// `reprlib`, `max_items` limits the number of items shown per collection and
+/// `max_string` the length of strings and other single values.
+///
+/// # Example
+///
+/// ```
+/// use pyprint::pretty::PrettyPrinter;
+///
+/// let v: Vec<u32> = (0..12).collect();
+/// let mut pp = PrettyPrinter::new();
+/// pp.set_width(20).set_compact(true);
+/// assert_eq!(pp.pformat(&v), "[0, 1, 2, 3, 4, 5,\n 6, 7, 8, 9, 10,\n 11]");
+/// assert_eq!(PrettyPrinter::new().set_depth(1).pformat(&[[1]]), "[[...]]");
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrettyPrinter {
+    width: usize,
+    indent: usize,
+    depth: Option<usize>,
+    max_items: Option<usize>,
+    max_string: Option<usize>,
+    compact: bool,
+    sort_dicts: bool,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrettyPrinter {
+    /// Creates a printer with Python's defaults.
+    pub fn new() -> Self {
+        PrettyPrinter {
+            width: 80,
+            indent: 1,
+            depth: None,
+            max_items: None,
+            max_string: None,
+            compact: false,
+            sort_dicts: true,
+        }
+    }
+
+    /// Sets the desired maximum line width.
+    pub fn set_width(&mut self, width: usize) -> &mut Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets the indentation added for each nesting level.
+    pub fn set_indent(&mut self, indent: usize) -> &mut Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets the number of nesting levels to show.
+    pub fn set_depth(&mut self, depth: usize) -> &mut Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Sets the number of items shown per collection; the rest are written as `... N more`.
+    pub fn set_max_items(&mut self, max_items: usize) -> &mut Self {
+        self.max_items = Some(max_items);
+        self
+    }
+
+    /// Sets the maximum length of strings and other single values.
+    ///
+    /// Values below 3, the length of the `...` that replaces the middle, are
+    /// treated as 3.
+    pub fn set_max_string(&mut self, max_string: usize) -> &mut Self {
+        self.max_string = Some(max_string.max(3));
+        self
+    }
+
+    /// Sets whether list items are packed onto as few lines as possible.
+    pub fn set_compact(&mut self, compact: bool) -> &mut Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Sets whether dict entries are sorted by key.
+    pub fn set_sort_dicts(&mut self, sort_dicts: bool) -> &mut Self {
+        self.sort_dicts = sort_dicts;
+        self
+    }
+
+    /// Returns the pretty-printed representation of a value, like `pprint.pformat`.
+    pub fn pformat<T: PyRepr + ?Sized>(&self, value: &T) -> String {
+        let mut out = String::new();
+        self.format(&self.tree(value), &mut out, 0, 0);
+        out
+    }
+
+    /// Returns the representation of a value on a single line, with this
+    /// printer's limits applied, like `reprlib.Repr.repr`.
+    pub fn saferepr<T: PyRepr + ?Sized>(&self, value: &T) -> String {
+        self.tree(value).flat()
+    }
+
+    fn tree<T: PyRepr + ?Sized>(&self, value: &T) -> Node {
+        let mut tree = Tree::new(self);
+        value.py_tree(&mut tree);
+        tree.pop()
+    }
+
+    /// Prints the pretty-printed representation of a value followed by a newline.
+    ///
+    /// Like the print macros, this starts from [`Printer::from_defaults`], so
+    /// it uses the default destination and `end`, and errors are reported and
+    /// recorded the same way.
+    ///
+    /// [`Printer::from_defaults`]: crate::Printer::from_defaults
+    pub fn pprint<T: PyRepr + ?Sized>(&self, value: &T) -> crate::Result<()> {
+        crate::Printer::from_defaults().print_args(&[format_args!("{}", self.pformat(value))])
+    }
+
+    fn format(&self, node: &Node, out: &mut String, indent: usize, allowance: usize) {
+        let rep = node.flat();
+        let max_width = self.width as isize - indent as isize - allowance as isize;
+        match node {
+            Node::Seq(kind, items) if !items.is_empty() && width(&rep) > max_width => {
+                let (open, close) = brackets(*kind);
+                out.push_str(open);
+                if *kind == Kind::Dict {
+                    out.extend(core::iter::repeat(' ').take(self.indent.saturating_sub(1)));
+                    self.format_entries(items, out, indent, allowance + 1);
+                    out.push_str(close);
+                } else {
+                    let close = if *kind == Kind::Tuple && items.len() == 1 { ",)" } else { close };
+                    self.format_items(items, out, indent, allowance + close.len());
+                    out.push_str(close);
+                }
+            }
+            _ => out.push_str(&rep),
+        }
+    }
+
+    fn format_items(&self, items: &[Node], out: &mut String, indent: usize, allowance: usize) {
+        let indent = indent + self.indent;
+        out.extend(core::iter::repeat(' ').take(self.indent.saturating_sub(1)));
+        let newline = newline_delimiter(indent);
+        let mut delim = "";
+        let mut max_width = self.width as isize - indent as isize + 1;
+        let mut width_left = max_width;
+        for (i, item) in items.iter().enumerate() {
+            let last = i + 1 == items.len();
+            if last {
+                max_width -= allowance as isize;
+                width_left -= allowance as isize;
+            }
+            if self.compact {
+                let rep = item.flat();
+                let w = width(&rep) + 2;
+                if width_left < w {
+                    width_left = max_width;
+                    if !delim.is_empty() {
+                        delim = &newline;
+                    }
+                }
+                if width_left >= w {
+                    width_left -= w;
+                    out.push_str(delim);
+                    delim = ", ";
+                    out.push_str(&rep);
+                    continue;
+                }
+            }
+            out.push_str(delim);
+            delim = &newline;
+            self.format(item, out, indent, if last { allowance } else { 1 });
+        }
+    }
+
+    fn format_entries(&self, entries: &[Node], out: &mut String, indent: usize, allowance: usize) {
+        let indent = indent + self.indent;
+        let newline = newline_delimiter(indent);
+        for (i, entry) in entries.iter().enumerate() {
+            let last = i + 1 == entries.len();
+            let allowance = if last { allowance } else { 1 };
+            match entry {
+                Node::Pair(key, value) => {
+                    let rep = key.flat();
+                    out.push_str(&rep);
+                    out.push_str(": ");
+                    self.format(value, out, indent + width(&rep) as usize + 2, allowance);
+                }
+                other => self.format(other, out, indent, allowance),
+            }
+            if !last {
+                out.push_str(&newline);
+            }
+        }
+    }
+}
+
+/// Returns the pretty-printed representation of a value with the default
+/// options, like Python's `pprint.pformat`.
+pub fn pformat<T: PyRepr + ?Sized>(value: &T) -> String {
+    PrettyPrinter::new().pformat(value)
+}
+
+/// Returns the representation of a value on a single line, like Python's
+/// `pprint.saferepr`.
+///
+/// Unlike [`repr`], this detects cycles through `Rc` and
+/// `Arc` pointers and writes them as `[...]`.
+///
+/// # Example
+///
+/// ```
+/// use pyprint::pretty::{saferepr, Tree};
+/// use pyprint::repr::PyRepr;
+/// use std::cell::RefCell;
+/// use std::fmt;
+/// use std::rc::Rc;
+///
+/// struct List(RefCell<Vec<Rc<List>>>);
+///
+/// impl PyRepr for List {
+///     fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         self.0.fmt_repr(f)
+///     }
+///
+///     fn py_tree(&self, tree: &mut Tree) {
+///         self.0.py_tree(tree)
+///     }
+/// }
+///
+/// let list = Rc::new(List(RefCell::new(Vec::new())));
+/// list.0.borrow_mut().push(list.clone());
+/// assert_eq!(saferepr(&list), "[[...]]");  // Like Python's `l = []; l.append(l)`
+/// # list.0.borrow_mut().clear();
+/// ```
+pub fn saferepr<T: PyRepr + ?Sized>(value: &T) -> String {
+    PrettyPrinter::new().saferepr(value)
+}
+
+/// The width of a string in characters, as Python's `len` counts it.
+fn width(s: &str) -> isize {
+    s.chars().count() as isize
+}
+
+fn newline_delimiter(indent: usize) -> String {
+    let mut delim = String::from(",\n");
+    delim.extend(core::iter::repeat(' ').take(indent));
+    delim
+}
+
+// Splits the arguments of `pp!` into printer options and print options
+#[doc(hidden)]
+#[macro_export]
+macro_rules! pp_variants {
+    (@process [$value:expr, [$($printer:tt)*], [$($opts:tt)*]] []) => {
+        $crate::pprint!($($printer)*.pformat(&$value), $($opts)*)
+    };
+
+    (@process [$value:expr, [$($printer:tt)*], $opts:tt] [width=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, [$($printer)*.set_width($e)], $opts] [$($rest)*])
+    };
+
+    (@process [$value:expr, [$($printer:tt)*], $opts:tt] [indent=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, [$($printer)*.set_indent($e)], $opts] [$($rest)*])
+    };
+
+    (@process [$value:expr, [$($printer:tt)*], $opts:tt] [depth=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, [$($printer)*.set_depth($e)], $opts] [$($rest)*])
+    };
+
+    (@process [$value:expr, [$($printer:tt)*], $opts:tt] [max_items=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, [$($printer)*.set_max_items($e)], $opts] [$($rest)*])
+    };
+
+    (@process [$value:expr, [$($printer:tt)*], $opts:tt] [max_string=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, [$($printer)*.set_max_string($e)], $opts] [$($rest)*])
+    };
+
+    (@process [$value:expr, [$($printer:tt)*], $opts:tt] [compact=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, [$($printer)*.set_compact($e)], $opts] [$($rest)*])
+    };
+
+    (@process [$value:expr, [$($printer:tt)*], $opts:tt] [sort_dicts=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, [$($printer)*.set_sort_dicts($e)], $opts] [$($rest)*])
+    };
+
+    (@process [$value:expr, $printer:tt, [$($opts:tt)*]] [end=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, $printer, [$($opts)* end=$e,]] [$($rest)*])
+    };
+
+    (@process [$value:expr, $printer:tt, [$($opts:tt)*]] [file=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, $printer, [$($opts)* file=$e,]] [$($rest)*])
+    };
+
+    (@process [$value:expr, $printer:tt, [$($opts:tt)*]] [flush=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, $printer, [$($opts)* flush=$e,]] [$($rest)*])
+    };
+
+    (@process [$value:expr, $printer:tt, [$($opts:tt)*]] [broken_pipe=$e:expr, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, $printer, [$($opts)* broken_pipe=$e,]] [$($rest)*])
+    };
+
+    (@process [$value:expr, $printer:tt, $opts:tt] [$name:ident=$e:expr, $($rest:tt)*]) => {
+        ::core::compile_error!(::core::concat!(
+            "unknown `pp!` option `", ::core::stringify!($name), "`; expected `width`, `indent`, `depth`, ",
+            "`max_items`, `max_string`, `compact`, `sort_dicts`, `end`, `file`, `flush` or `broken_pipe`"
+        ))
+    };
+
+    (@process [$value:expr, $printer:tt, $opts:tt] [, $($rest:tt)*]) => {
+        $crate::pp_variants!(@process [$value, $printer, $opts] [$($rest)*])
+    };
+}
+
+/// Pretty-prints a value like Python's `pprint.pp`.
+///
+/// Accepts the [`PrettyPrinter`](crate::pretty::PrettyPrinter) options
+/// `width`, `indent`, `depth`, `max_items`, `max_string`, `compact` and
+/// `sort_dicts`, and the `end`, `file`, `flush` and `broken_pipe` options of
+/// `pprint!`; any other option is a compile error. Returns a `Result` like
+/// `pprint!`.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use pyprint::pp;
+/// use std::collections::BTreeMap;
+///
+/// let config = BTreeMap::from([("name", vec!["server"]), ("ports", vec!["8080", "8081"])]);
+/// pp!(config).unwrap();  // Prints: {'name': ['server'], 'ports': ['8080', '8081']}
+///
+/// let mut out = String::new();
+/// pp!(config, width=30, file=&mut out).unwrap();
+/// assert_eq!(out, "{'name': ['server'],\n 'ports': ['8080', '8081']}\n");
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// pyprint::pp!([1, 2], widht=40);
+/// ```
+#[macro_export]
+macro_rules! pp {
+    ($value:expr $(, $($t:tt)*)?) => {
+        $crate::pp_variants!(@process [$value, [$crate::pretty::PrettyPrinter::new()], []] [$($($t)*)?,])
+    };
+}
+
+#[cfg(test)]
+use alloc::collections::{BTreeMap, BTreeSet};
+
+#[cfg(test)]
+type Sample = BTreeMap<&'static str, (Vec<u32>, Option<(u8,)>, BTreeSet<char>)>;
+
+#[cfg(test)]
+fn sample() -> Sample {
+    BTreeMap::from([
+        ("nested", ((1..=20).collect(), Some((1,)), BTreeSet::from(['x', 'y']))),
+        ("ports", (Vec::from([8080, 8081]), None, BTreeSet::new())),
+    ])
+}
+
+#[test]
+fn test_pformat() {
+    let mut pp = PrettyPrinter::new();
+    assert_eq!(
+        pp.pformat(&sample()),
+        "{'nested': ([1,\n             2,\n             3,\n             4,\n             5,\n             6,\n             \
+         7,\n             8,\n             9,\n             10,\n             11,\n             12,\n             \
+         13,\n             14,\n             15,\n             16,\n             17,\n             18,\n             \
+         19,\n             20],\n            (1,),\n            {'x', 'y'}),\n 'ports': ([8080, 8081], None, set())}"
+    );
+    pp.set_width(40).set_indent(4).set_compact(true);
+    assert_eq!(
+        pp.pformat(&sample()),
+        "{   'nested': (   [   1, 2, 3, 4, 5, 6,\n                      7, 8, 9, 10, 11,\n                      \
+         12, 13, 14, 15,\n                      16, 17, 18, 19,\n                      20],\n                  \
+         (1,), {'x', 'y'}),\n    'ports': (   [8080, 8081], None,\n                 set())}"
+    );
+    assert_eq!(PrettyPrinter::new().set_width(5).pformat(&((1, 2),)), "((1,\n  2),)");
+    assert_eq!(PrettyPrinter::new().set_depth(1).pformat(&sample()), "{'nested': (...), 'ports': (...)}");
+    assert_eq!(PrettyPrinter::new().pformat("text"), "'text'");
+}
+
+#[test]
+fn test_sort_dicts() {
+    struct Unsorted;
+
+    impl PyRepr for Unsorted {
+        fn fmt_repr(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str("{10: 1, 9: 2}")
+        }
+
+        fn py_tree(&self, tree: &mut Tree) {
+            tree.seq(Kind::Dict, |t| {
+                t.entry(&10, &1);
+                t.entry(&9, &2);
+            });
+        }
+    }
+
+    let mut pp = PrettyPrinter::new();
+    assert_eq!(pp.pformat(&Unsorted), "{9: 2, 10: 1}");
+    assert_eq!(pp.set_width(5).pformat(&Unsorted), "{9: 2,\n 10: 1}");
+    assert_eq!(pp.set_sort_dicts(false).pformat(&Unsorted), "{10: 1,\n 9: 2}");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_pp() {
+    let mut out = String::new();
+    pp!(sample(), width=40, depth=2, file=&mut out, end="|").unwrap();
+    assert_eq!(out, "{'nested': ([...], (...), {...}),\n 'ports': ([...], None, {...})}|");
+}
+
+#[test]
+fn test_limits() {
+    let long: Vec<u32> = (1..=1000).collect();
+    let mut pp = PrettyPrinter::new();
+    pp.set_max_items(2).set_max_string(10);
+    assert_eq!(pp.saferepr(&long), "[1, 2, ... 998 more]");
+    assert_eq!(pp.saferepr(&("abcdefghijklmnop", "short")), "('ab...nop', 'short')");
+    assert_eq!(pp.saferepr(&BTreeMap::from([(1, 'a'), (2, 'b'), (3, 'c')])), "{1: 'a', 2: 'b', ... 1 more}");
+    assert_eq!(pp.set_width(12).pformat(&[[1, 2, 3]]), "[[1,\n  2,\n  ... 1 more]]");
+    assert_eq!(PrettyPrinter::new().set_depth(2).saferepr(&[[[1]]]), "[[[...]]]");
+    assert_eq!(saferepr(&long[..3]), "[1, 2, 3]");
+    assert_eq!(PrettyPrinter::new().set_max_string(1).saferepr(&"abcdef"), "...");
+    assert_eq!(PrettyPrinter::new().set_max_string(4).saferepr(&"abcdef"), "...'");
+}
+
+#[test]
+fn test_cycles() {
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    enum Value {
+        Int(i32),
+        List(Rc<RefCell<Vec<Value>>>),
+    }
+
+    impl PyRepr for Value {
+        fn fmt_repr(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                Value::Int(v) => v.fmt_repr(f),
+                Value::List(l) => l.fmt_repr(f),
+            }
+        }
+
+        fn py_tree(&self, tree: &mut Tree) {
+            match self {
+                Value::Int(v) => v.py_tree(tree),
+                Value::List(l) => l.py_tree(tree),
+            }
+        }
+    }
+
+    let a = Rc::new(RefCell::new(Vec::from([Value::Int(1)])));
+    let b = Rc::new(RefCell::new(Vec::from([Value::List(a.clone())])));
+    a.borrow_mut().push(Value::List(b.clone()));
+    // Shared but acyclic values are written in full
+    a.borrow_mut().push(Value::List(Rc::new(RefCell::new(Vec::new()))));
+    let shared = Rc::new(RefCell::new(Vec::from([Value::Int(2)])));
+    let c = Vec::from([Value::List(shared.clone()), Value::List(shared)]);
+    assert_eq!(saferepr(&a), "[1, [[...]], []]");
+    assert_eq!(pformat(&Value::List(b.clone())), "[[1, [...], []]]");
+    assert_eq!(PrettyPrinter::new().set_width(8).pformat(&a), "[1,\n [[...]],\n []]");
+    assert_eq!(saferepr(&c), "[[2], [2]]");
+    a.borrow_mut().clear();
+}
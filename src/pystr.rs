@@ -0,0 +1,152 @@
+//! Python `str()` rendering of Rust values.
+//!
+//! [`PyStr`] writes values the way Python's `str()` writes the corresponding
+//! Python objects. Numbers, booleans and strings print as themselves, with
+//! floats in Python's shortest round-trip form (`1.0`, `1e+16`, `inf`, `nan`,
+//! `-0.0`). `Option::None` prints as `None`. Containers print like their
+//! Python counterparts, whose `str()` is their [`repr()`](crate::repr), so
+//! tuples, lists, dicts and sets only need their elements to implement
+//! [`PyRepr`].
+//!
+//! `pprint!(pystr=true, ...)` uses this trait instead of `Display`.
+//!
+//! ```
+//! use pyprint::pystr::PyStr;
+//!
+//! assert_eq!(1.0.str().to_string(), "1.0");
+//! assert_eq!(Some("a").str().to_string(), "a");
+//! assert_eq!(vec![Some("a"), None].str().to_string(), "['a', None]");
+//! ```
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::repr::PyRepr;
+
+/// Rendering like Python's `str()`.
+pub trait PyStr {
+    /// Writes the Python string form of `self`.
+    fn fmt_str(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Returns a wrapper whose `Display` implementation writes the Python string form.
+    fn str(&self) -> Str<'_, Self> {
+        Str(self)
+    }
+}
+
+/// Displays a value with [`PyStr`].
+pub struct Str<'a, T: ?Sized>(pub &'a T);
+
+impl<T: PyStr + ?Sized> fmt::Display for Str<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_str(f)
+    }
+}
+
+macro_rules! impl_py_str_display {
+    ($($t:ty)*) => {
+        $(
+            impl PyStr for $t {
+                fn fmt_str(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Display::fmt(self, f)
+                }
+            }
+        )*
+    };
+}
+
+impl_py_str_display!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize char str String);
+
+// Types whose `str()` is their `repr()`
+macro_rules! impl_py_str_repr {
+    ($($(#[$attr:meta])* [$($gen:tt)*] $t:ty,)*) => {
+        $(
+            $(#[$attr])*
+            impl<$($gen)*> PyStr for $t where $t: PyRepr {
+                fn fmt_str(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    self.fmt_repr(f)
+                }
+            }
+        )*
+    };
+}
+
+impl_py_str_repr! {
+    [] f32,
+    [] f64,
+    [] bool,
+    [] (),
+    [A] (A,),
+    [A, B] (A, B),
+    [A, B, C] (A, B, C),
+    [A, B, C, D] (A, B, C, D),
+    [A, B, C, D, E] (A, B, C, D, E),
+    [A, B, C, D, E, F] (A, B, C, D, E, F),
+    [A, B, C, D, E, F, G] (A, B, C, D, E, F, G),
+    [A, B, C, D, E, F, G, H] (A, B, C, D, E, F, G, H),
+    [A, B, C, D, E, F, G, H, I] (A, B, C, D, E, F, G, H, I),
+    [A, B, C, D, E, F, G, H, I, J] (A, B, C, D, E, F, G, H, I, J),
+    [A, B, C, D, E, F, G, H, I, J, K] (A, B, C, D, E, F, G, H, I, J, K),
+    [A, B, C, D, E, F, G, H, I, J, K, L] (A, B, C, D, E, F, G, H, I, J, K, L),
+    [T] [T],
+    [T, const N: usize] [T; N],
+    [T] Vec<T>,
+    [T] VecDeque<T>,
+    [K, V] BTreeMap<K, V>,
+    [T] BTreeSet<T>,
+    #[cfg(feature = "std")]
+    [K, V, S] std::collections::HashMap<K, V, S>,
+    #[cfg(feature = "std")]
+    [T, S] std::collections::HashSet<T, S>,
+}
+
+impl<T: PyStr> PyStr for Option<T> {
+    fn fmt_str(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(v) => v.fmt_str(f),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+macro_rules! impl_py_str_deref {
+    ($($t:ty)*) => {
+        $(
+            impl<T: PyStr + ?Sized> PyStr for $t {
+                fn fmt_str(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    (**self).fmt_str(f)
+                }
+            }
+        )*
+    };
+}
+
+impl_py_str_deref!(&T &mut T Box<T> Rc<T> Arc<T>);
+
+#[test]
+fn test_py_str() {
+    use alloc::string::ToString;
+
+    assert_eq!(1.0.str().to_string(), "1.0");
+    assert_eq!(1e16.str().to_string(), "1e+16");
+    assert_eq!(123456789012345.0.str().to_string(), "123456789012345.0");
+    assert_eq!(0.0001.str().to_string(), "0.0001");
+    assert_eq!(1e-5.str().to_string(), "1e-05");
+    assert_eq!((-0.0).str().to_string(), "-0.0");
+    assert_eq!(f64::INFINITY.str().to_string(), "inf");
+    assert_eq!(f64::NAN.str().to_string(), "nan");
+    assert_eq!(0.1f32.str().to_string(), "0.1");
+    assert_eq!(false.str().to_string(), "False");
+    assert_eq!("it's".str().to_string(), "it's");
+    assert_eq!(Some(Some('x')).str().to_string(), "x");
+    assert_eq!(None::<i32>.str().to_string(), "None");
+    assert_eq!((1, "a").str().to_string(), "(1, 'a')");
+    assert_eq!(Vec::from([1.0, 2.5]).str().to_string(), "[1.0, 2.5]");
+    assert_eq!(BTreeMap::from([("k", None::<i32>)]).str().to_string(), "{'k': None}");
+    assert_eq!(Box::new(String::from("s")).str().to_string(), "s");
+}
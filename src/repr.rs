@@ -0,0 +1,464 @@
+//! Python `repr()` rendering of Rust values.
+//!
+//! [`PyRepr`] writes values the way Python's `repr()` writes the
+//! corresponding Python objects: strings in single quotes with Python
+//! escapes, `True`/`False`, `None` for `Option::None`, tuples, lists for
+//! slices and `Vec`s, dicts for maps and sets for sets. The
+//! [`rprint!`](crate::rprint) macro prints its arguments this way.
+//!
+//! ```
+//! use pyprint::repr::PyRepr;
+//!
+//! let v = (Some("a'b"), None::<i32>, vec![1.0, 2.5], true);
+//! assert_eq!(v.repr().to_string(), r#"("a'b", None, [1.0, 2.5], True)"#);
+//! ```
+//!
+//! `repr()` follows `Rc` and `Arc` pointers without checking for cycles; use
+//! [`saferepr`](crate::pretty::saferepr) for structures that may contain them.
+//!
+//! Python dicts and sets keep their insertion order, which `HashMap` and
+//! `HashSet` do not track. Their entries are sorted by key so that output is
+//! deterministic, in the order [`pformat`](crate::pretty::pformat) sorts dict
+//! keys: numbers by value, strings by text and tuples item by item.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::rc::{self, Rc};
+use alloc::string::String;
+use alloc::sync::{self, Arc};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+
+use crate::format::{float_repr, write_str_repr, FormatArg, ToFormatArg};
+#[cfg(feature = "std")]
+use crate::pretty::sorted;
+use crate::pretty::{Kind, Tree};
+
+/// Rendering like Python's `repr()`.
+pub trait PyRepr {
+    /// Writes the Python representation of `self`.
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Describes the structure of `self` to the [`PrettyPrinter`](crate::pretty::PrettyPrinter).
+    ///
+    /// The default adds `self` as an atom, written as a whole with
+    /// [`PyRepr::fmt_repr`]. Containers add their items instead, so that they
+    /// can be broken over several lines.
+    fn py_tree(&self, tree: &mut Tree) {
+        tree.atom(self);
+    }
+
+    /// Returns a wrapper whose `Display` implementation writes the Python representation.
+    fn repr(&self) -> Repr<'_, Self> {
+        Repr(self)
+    }
+}
+
+/// Displays a value with [`PyRepr`].
+pub struct Repr<'a, T: ?Sized>(pub &'a T);
+
+impl<T: PyRepr + ?Sized> fmt::Display for Repr<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_repr(f)
+    }
+}
+
+macro_rules! impl_py_repr_display {
+    ($($t:ty)*) => {
+        $(
+            impl PyRepr for $t {
+                fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Display::fmt(self, f)
+                }
+            }
+        )*
+    };
+}
+
+impl_py_repr_display!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+impl PyRepr for f64 {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&float_repr(*self))
+    }
+}
+
+impl PyRepr for f32 {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_format_arg() {
+            FormatArg::Float(v) => f.write_str(&float_repr(v)),
+            _ => unreachable!("f32 converts to a float argument"),
+        }
+    }
+}
+
+impl PyRepr for bool {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if *self { "True" } else { "False" })
+    }
+}
+
+impl PyRepr for char {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_str_repr(f, self.encode_utf8(&mut [0; 4]), false)
+    }
+}
+
+impl PyRepr for str {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_str_repr(f, self, false)
+    }
+}
+
+impl PyRepr for String {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_str_repr(f, self, false)
+    }
+}
+
+impl PyRepr for () {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("()")
+    }
+}
+
+impl<T: PyRepr> PyRepr for Option<T> {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(v) => v.fmt_repr(f),
+            None => f.write_str("None"),
+        }
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        match self {
+            Some(v) => v.py_tree(tree),
+            None => tree.atom(self),
+        }
+    }
+}
+
+macro_rules! impl_py_repr_deref {
+    ($($t:ty)*) => {
+        $(
+            impl<T: PyRepr + ?Sized> PyRepr for $t {
+                fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    (**self).fmt_repr(f)
+                }
+
+                fn py_tree(&self, tree: &mut Tree) {
+                    (**self).py_tree(tree)
+                }
+            }
+        )*
+    };
+}
+
+impl_py_repr_deref!(&T &mut T Box<T>);
+
+macro_rules! impl_py_repr_shared {
+    ($($t:ty)*) => {
+        $(
+            impl<T: PyRepr + ?Sized> PyRepr for $t {
+                fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    (**self).fmt_repr(f)
+                }
+
+                fn py_tree(&self, tree: &mut Tree) {
+                    tree.shared(&**self)
+                }
+            }
+        )*
+    };
+}
+
+// Shared pointers let the pretty printer detect cycles
+impl_py_repr_shared!(Rc<T> Arc<T>);
+
+impl<T: PyRepr + ?Sized> PyRepr for RefCell<T> {
+    /// Writes the value, or `<borrowed>` if it is mutably borrowed.
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_borrow() {
+            Ok(v) => v.fmt_repr(f),
+            Err(_) => f.write_str("<borrowed>"),
+        }
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        match self.try_borrow() {
+            Ok(v) => v.py_tree(tree),
+            Err(_) => tree.atom(self),
+        }
+    }
+}
+
+macro_rules! impl_py_repr_weak {
+    ($($t:ty)*) => {
+        $(
+            impl<T: PyRepr + ?Sized> PyRepr for $t {
+                /// Writes the value, or `None` if it has been dropped.
+                fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    self.upgrade().fmt_repr(f)
+                }
+
+                fn py_tree(&self, tree: &mut Tree) {
+                    self.upgrade().py_tree(tree)
+                }
+            }
+        )*
+    };
+}
+
+impl_py_repr_weak!(rc::Weak<T> sync::Weak<T>);
+
+macro_rules! impl_py_repr_tuple {
+    ($(($($name:ident)+))*) => {
+        $(
+            impl<$($name: PyRepr),+> PyRepr for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    let ($($name,)+) = self;
+                    let mut seq = Seq::new(f, "(")?;
+                    $(seq.entry($name)?;)+
+                    if seq.count == 1 {
+                        seq.f.write_str(",")?;
+                    }
+                    seq.finish(")")
+                }
+
+                #[allow(non_snake_case)]
+                fn py_tree(&self, tree: &mut Tree) {
+                    let ($($name,)+) = self;
+                    tree.seq(Kind::Tuple, |t| {
+                        $(t.item($name);)+
+                    });
+                }
+            }
+        )*
+    };
+}
+
+impl_py_repr_tuple! {
+    (A)
+    (A B)
+    (A B C)
+    (A B C D)
+    (A B C D E)
+    (A B C D E F)
+    (A B C D E F G)
+    (A B C D E F G H)
+    (A B C D E F G H I)
+    (A B C D E F G H I J)
+    (A B C D E F G H I J K)
+    (A B C D E F G H I J K L)
+}
+
+impl<T: PyRepr> PyRepr for [T] {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_list(f, self.iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        tree.seq(Kind::List, |t| self.iter().for_each(|v| t.item(v)));
+    }
+}
+
+impl<T: PyRepr, const N: usize> PyRepr for [T; N] {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_list(f, self.iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        tree.seq(Kind::List, |t| self.iter().for_each(|v| t.item(v)));
+    }
+}
+
+impl<T: PyRepr> PyRepr for Vec<T> {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_list(f, self.iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        tree.seq(Kind::List, |t| self.iter().for_each(|v| t.item(v)));
+    }
+}
+
+impl<T: PyRepr> PyRepr for VecDeque<T> {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_list(f, self.iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        tree.seq(Kind::List, |t| self.iter().for_each(|v| t.item(v)));
+    }
+}
+
+impl<K: PyRepr, V: PyRepr> PyRepr for BTreeMap<K, V> {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_dict(f, self.iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        tree.seq(Kind::Dict, |t| self.iter().for_each(|(k, v)| t.entry(k, v)));
+    }
+}
+
+impl<T: PyRepr> PyRepr for BTreeSet<T> {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_set(f, self.iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        tree.seq(Kind::Set, |t| self.iter().for_each(|v| t.item(v)));
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: PyRepr, V: PyRepr, S> PyRepr for std::collections::HashMap<K, V, S> {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_dict(f, sorted(self.iter(), |&(k, _)| k).into_iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        let entries = sorted(self.iter(), |&(k, _)| k);
+        tree.seq(Kind::Dict, |t| entries.iter().for_each(|(k, v)| t.entry(k, v)));
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: PyRepr, S> PyRepr for std::collections::HashSet<T, S> {
+    fn fmt_repr(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_set(f, sorted(self.iter(), |&v| v).into_iter())
+    }
+
+    fn py_tree(&self, tree: &mut Tree) {
+        let items = sorted(self.iter(), |&v| v);
+        tree.seq(Kind::Set, |t| items.iter().for_each(|v| t.item(v)));
+    }
+}
+
+/// Writes comma separated entries between brackets.
+struct Seq<'f, 'a> {
+    f: &'f mut fmt::Formatter<'a>,
+    count: usize,
+}
+
+impl<'f, 'a> Seq<'f, 'a> {
+    fn new(f: &'f mut fmt::Formatter<'a>, open: &str) -> Result<Self, fmt::Error> {
+        f.write_str(open)?;
+        Ok(Seq { f, count: 0 })
+    }
+
+    fn separator(&mut self) -> fmt::Result {
+        self.count += 1;
+        if self.count > 1 {
+            self.f.write_str(", ")?;
+        }
+        Ok(())
+    }
+
+    fn entry(&mut self, value: &(impl PyRepr + ?Sized)) -> fmt::Result {
+        self.separator()?;
+        value.fmt_repr(self.f)
+    }
+
+    fn pair(&mut self, key: &(impl PyRepr + ?Sized), value: &(impl PyRepr + ?Sized)) -> fmt::Result {
+        self.separator()?;
+        key.fmt_repr(self.f)?;
+        self.f.write_str(": ")?;
+        value.fmt_repr(self.f)
+    }
+
+    fn finish(self, close: &str) -> fmt::Result {
+        self.f.write_str(close)
+    }
+}
+
+fn write_list<T: PyRepr>(f: &mut fmt::Formatter<'_>, items: impl Iterator<Item = T>) -> fmt::Result {
+    let mut seq = Seq::new(f, "[")?;
+    for item in items {
+        seq.entry(&item)?;
+    }
+    seq.finish("]")
+}
+
+fn write_dict<K: PyRepr, V: PyRepr>(f: &mut fmt::Formatter<'_>, entries: impl Iterator<Item = (K, V)>) -> fmt::Result {
+    let mut seq = Seq::new(f, "{")?;
+    for (k, v) in entries {
+        seq.pair(&k, &v)?;
+    }
+    seq.finish("}")
+}
+
+fn write_set<T: PyRepr>(f: &mut fmt::Formatter<'_>, items: impl Iterator<Item = T>) -> fmt::Result {
+    let mut items = items.peekable();
+    if items.peek().is_none() {
+        return f.write_str("set()");
+    }
+    let mut seq = Seq::new(f, "{")?;
+    for item in items {
+        seq.entry(&item)?;
+    }
+    seq.finish("}")
+}
+
+/// Returns the Python representation of a value as a `String`.
+///
+/// # Example
+///
+/// ```
+/// use pyprint::repr::repr;
+///
+/// assert_eq!(repr(&vec![Some('a'), None]), "['a', None]");
+/// ```
+pub fn repr<T: PyRepr + ?Sized>(value: &T) -> String {
+    alloc::format!("{}", value.repr())
+}
+
+#[test]
+fn test_repr() {
+    assert_eq!(repr(&1), "1");
+    assert_eq!(repr(&-2.0), "-2.0");
+    assert_eq!(repr(&0.1f32), "0.1");
+    assert_eq!(repr(&1e16), "1e+16");
+    assert_eq!(repr(&f64::NAN), "nan");
+    assert_eq!(repr(&true), "True");
+    assert_eq!(repr("it's \"x\"\n"), r#"'it\'s "x"\n'"#);
+    assert_eq!(repr(&String::from("a\"b")), r#"'a"b'"#);
+    assert_eq!(repr(&'\''), r#""'""#);
+    assert_eq!(repr(&Some("a")), "'a'");
+    assert_eq!(repr(&None::<u8>), "None");
+    assert_eq!(repr(&()), "()");
+    assert_eq!(repr(&(1,)), "(1,)");
+    assert_eq!(repr(&(1, "a", [true])), "(1, 'a', [True])");
+    assert_eq!(repr(&Vec::<i32>::new()), "[]");
+    assert_eq!(repr(&[1, 2][..]), "[1, 2]");
+    let mut map = BTreeMap::new();
+    map.insert("k", Vec::from([(1, 2.5)]));
+    map.insert("a", Vec::new());
+    assert_eq!(repr(&map), "{'a': [], 'k': [(1, 2.5)]}");
+    assert_eq!(repr(&BTreeSet::<i32>::new()), "set()");
+    assert_eq!(repr(&BTreeSet::from([3, 1])), "{1, 3}");
+    assert_eq!(repr(&Box::new(Rc::new("x"))), "'x'");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_repr_hash_collections() {
+    use std::collections::{HashMap, HashSet};
+
+    let map: HashMap<_, _> = [("b", 2), ("a", 1), ("c", 3)].into_iter().collect();
+    assert_eq!(repr(&map), "{'a': 1, 'b': 2, 'c': 3}");
+    assert_eq!(repr(&HashMap::<i32, i32>::new()), "{}");
+    let set: HashSet<_> = ["y", "x"].into_iter().collect();
+    assert_eq!(repr(&set), "{'x', 'y'}");
+    assert_eq!(repr(&HashSet::<i32>::new()), "set()");
+
+    // Numbers sort by value, not by their text
+    let map: HashMap<_, _> = [(10, "b"), (9, "a"), (100, "c"), (-5, "d")].into_iter().collect();
+    assert_eq!(repr(&map), "{-5: 'd', 9: 'a', 10: 'b', 100: 'c'}");
+    let set: HashSet<_> = [(2, 10), (2, 9), (11, 0)].into_iter().collect();
+    assert_eq!(repr(&set), "{(2, 9), (2, 10), (11, 0)}");
+    assert_eq!(crate::pretty::PrettyPrinter::new().set_sort_dicts(false).pformat(&map), repr(&map));
+}
@@ -0,0 +1,114 @@
+//! Per-thread record of the last print result.
+
+use std::cell::RefCell;
+use std::io::{self, ErrorKind, Result};
+
+use crate::error::{flush_error, FlushError, FormatFailed};
+
+thread_local! {
+    static LAST_PRINTER_RESULT: RefCell<Result<()>> = const { RefCell::new(Ok(())) };
+}
+
+/// Makes an owned copy of an I/O error, keeping its kind, message and OS error
+/// code, and the markers of flush and formatting errors.
+fn duplicate_error(err: &io::Error) -> io::Error {
+    let inner = err.get_ref();
+    if let Some(flush) = inner.and_then(|inner| inner.downcast_ref::<FlushError>()) {
+        return flush_error(duplicate_error(flush.io_error()));
+    }
+    if inner.is_some_and(|inner| inner.is::<FormatFailed>()) {
+        return io::Error::new(err.kind(), FormatFailed);
+    }
+    match err.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(err.kind(), err.to_string()),
+    }
+}
+
+/// Stores the outcome of a print operation for the current thread.
+pub(crate) fn record_result(res: &Result<()>) {
+    let copy = match res {
+        Ok(()) => Ok(()),
+        Err(e) => Err(duplicate_error(e)),
+    };
+    // The thread-local may already be gone when printing from a destructor at thread exit.
+    let _ = LAST_PRINTER_RESULT.try_with(|last| *last.borrow_mut() = copy);
+}
+
+/// Returns the result of the last print operation on the current thread.
+/// 
+/// This function is useful for error handling when using the unwrapping variants
+/// of the print macros. The stored result is left in place; the returned error
+/// is a copy with the same kind and message.
+/// 
+/// # Returns
+/// 
+/// The `Result` from the last printing operation.
+pub fn last_printer_result() -> Result<()> {
+    LAST_PRINTER_RESULT.with(|last| match &*last.borrow() {
+        Ok(()) => Ok(()),
+        Err(e) => Err(duplicate_error(e)),
+    })
+}
+
+/// Takes the result of the last print operation on the current thread.
+/// 
+/// The stored result is reset to `Ok(())`, so a second call returns `Ok(())`
+/// until another print fails.
+/// 
+/// # Example
+/// 
+/// ```
+/// use pyprint::{pprint, take_last_printer_result};
+/// 
+/// let _ = pprint!(file=Vec::new(), "Hello");
+/// assert!(take_last_printer_result().is_ok());
+/// ```
+pub fn take_last_printer_result() -> Result<()> {
+    LAST_PRINTER_RESULT.with(|last| last.replace(Ok(())))
+}
+
+/// Reports the error kind of the last print operation on the current thread
+/// without consuming it.
+pub fn peek_last_printer_result() -> std::result::Result<(), ErrorKind> {
+    LAST_PRINTER_RESULT.with(|last| match &*last.borrow() {
+        Ok(()) => Ok(()),
+        Err(e) => Err(e.kind()),
+    })
+}
+
+#[test]
+fn test_last_printer_result() {
+    use std::fmt;
+
+    use crate::error::{is_flush_error, Context, PrintError};
+    use crate::{pprint, FlushCounter};
+
+    struct Failing;
+
+    impl fmt::Display for Failing {
+        fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
+    let mut file = FlushCounter::new();
+    file.fail_flush = true;
+    let _ = pprint!(1, flush=true, file=&mut file);
+    let err = last_printer_result().unwrap_err();
+    assert!(is_flush_error(&err));
+    let context = Context::new("pprint!", file!(), line!());
+    let err = PrintError::new(err, context);
+    assert!(matches!(err, PrintError::Flush(..)));
+    assert!(err.is_flush_error());
+
+    let _ = pprint!(Failing, file=&mut file);
+    let err = last_printer_result().unwrap_err();
+    assert!(!is_flush_error(&err));
+    let err = PrintError::new(err, context);
+    assert!(matches!(err, PrintError::Format(..)));
+    assert!(!err.is_flush_error());
+
+    let _ = pprint!(file=std::io::sink());
+    assert!(last_printer_result().is_ok());
+}
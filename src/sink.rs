@@ -0,0 +1,433 @@
+//! Output destinations for [`Printer`](crate::Printer).
+//!
+//! A printer writes through the [`Sink`] trait, which is implemented by adapters
+//! over both `std::io::Write` (`IoSink`) and `core::fmt::Write` ([`FmtSink`]).
+//! [`IntoSink`] picks the right adapter, so the `file=` option accepts a `File`,
+//! a `&mut Vec<u8>`, a `&mut String` or a `&mut fmt::Formatter` alike.
+//! Without the `std` feature only the `core::fmt::Write` adapter is available.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+use alloc::string::String;
+
+use crate::Result;
+
+/// An output destination that a [`Printer`](crate::Printer) can write to.
+pub trait Sink {
+    /// Writes a string slice to the destination.
+    fn write_str(&mut self, s: &str) -> Result<()>;
+
+    /// Writes formatted arguments to the destination without allocating.
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<()> {
+        let mut adapter = Adapter { sink: self, res: Ok(()) };
+        match fmt::write(&mut adapter, args) {
+            Ok(()) => Ok(()),
+            Err(_) => match adapter.res {
+                Err(e) => Err(e),
+                Ok(()) => Err(fmt_error()),
+            },
+        }
+    }
+
+    /// Flushes any buffered output.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Called after each print has written its `end`, before the print is
+    /// flushed with `flush=true`.
+    ///
+    /// Buffering sinks use this to apply their flush policy (see
+    /// `buffer::Buffered`). Does nothing by default.
+    fn end_print(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether other writers may access the destination at the same time.
+    ///
+    /// Output for a shared destination is assembled per print and handed to
+    /// [`Sink::write_str`] in one piece, so concurrent prints do not interleave.
+    /// Prints longer than 8 KiB are handed over in several pieces of at most
+    /// 8 KiB each (or one longer piece for a single longer element), which
+    /// other prints may come between. Defaults to `false`.
+    fn is_shared(&self) -> bool {
+        false
+    }
+
+    /// Whether this destination is the process's stdout or stderr.
+    ///
+    /// Prints to such destinations follow the process-wide broken pipe
+    /// policy (see `error::set_broken_pipe_policy`). Defaults to `false`.
+    fn is_std_stream(&self) -> bool {
+        false
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for &mut S {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        (**self).write_str(s)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<()> {
+        (**self).write_fmt(args)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+
+    fn end_print(&mut self) -> Result<()> {
+        (**self).end_print()
+    }
+
+    fn is_shared(&self) -> bool {
+        (**self).is_shared()
+    }
+
+    fn is_std_stream(&self) -> bool {
+        (**self).is_std_stream()
+    }
+}
+
+/// Largest chunk a print assembles before handing it to a shared destination.
+const ASSEMBLY_LIMIT: usize = 8 * 1024;
+
+/// Collects the output of one print operation for a shared destination.
+///
+/// Output is written in one piece when the print finishes, or in chunks of
+/// about [`ASSEMBLY_LIMIT`] bytes for longer prints.
+pub(crate) struct Assembled<'s> {
+    sink: &'s mut dyn Sink,
+    buf: String,
+}
+
+impl<'s> Assembled<'s> {
+    pub(crate) fn new(sink: &'s mut dyn Sink) -> Self {
+        Assembled { sink, buf: String::new() }
+    }
+
+    fn write_out(&mut self) -> Result<()> {
+        if !self.buf.is_empty() {
+            self.sink.write_str(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes out whatever has not been written yet.
+    pub(crate) fn finish(mut self) -> Result<()> {
+        self.write_out()
+    }
+}
+
+impl Sink for Assembled<'_> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        if self.buf.len() + s.len() > ASSEMBLY_LIMIT {
+            self.write_out()?;
+            if s.len() > ASSEMBLY_LIMIT {
+                return self.sink.write_str(s);
+            }
+        }
+        self.buf.push_str(s);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.write_out()?;
+        self.sink.flush()
+    }
+
+    fn end_print(&mut self) -> Result<()> {
+        self.write_out()?;
+        self.sink.end_print()
+    }
+}
+
+/// Forwards `fmt::Write` calls to a sink, keeping the sink's error.
+struct Adapter<'s, S: ?Sized> {
+    sink: &'s mut S,
+    res: Result<()>,
+}
+
+impl<S: Sink + ?Sized> fmt::Write for Adapter<'_, S> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.sink.write_str(s).map_err(|e| {
+            self.res = Err(e);
+            fmt::Error
+        })
+    }
+}
+
+/// Adapter that turns a `std::io::Write` into a [`Sink`].
+///
+/// Handles to stdout and stderr count as [shared](Sink::is_shared); other
+/// writers are assumed to be private to the printer. Wrap writers that other
+/// threads write to as well in [`SharedIoSink`].
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct IoSink<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: io::Write> Sink for IoSink<W> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        self.0.write_all(s.as_bytes())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+
+    fn is_shared(&self) -> bool {
+        is_std_stream::<W>()
+    }
+
+    fn is_std_stream(&self) -> bool {
+        is_std_stream::<W>()
+    }
+}
+
+/// Adapter that turns a `std::io::Write` shared with other threads or
+/// processes into a [`Sink`], so that each print is written as one unit.
+///
+/// ```
+/// use std::fs::OpenOptions;
+/// use pyprint::pprn;
+/// use pyprint::sink::SharedIoSink;
+///
+/// let path = std::env::temp_dir().join("pyprint-shared.log");
+/// let log = OpenOptions::new().create(true).append(true).open(path).unwrap();
+/// pprn!(file=SharedIoSink(&log), "written", "in", "one", "piece");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct SharedIoSink<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: io::Write> Sink for SharedIoSink<W> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        self.0.write_all(s.as_bytes())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+
+    fn is_std_stream(&self) -> bool {
+        is_std_stream::<W>()
+    }
+}
+
+/// Whether `W` is a handle to the process's stdout or stderr: `Stdout`,
+/// `Stderr`, `StdoutLock` or `StderrLock`, a reference to one, or one of
+/// them in a `BufWriter` or `LineWriter`.
+///
+/// Other writers, including other wrappers around these handles, do not
+/// count; prints to them are written without being assembled first and
+/// follow their own broken pipe policy.
+#[cfg(feature = "std")]
+pub(crate) fn is_std_stream<W: ?Sized>() -> bool {
+    use core::any::TypeId;
+    use std::io::{BufWriter, LineWriter, Stderr, StderrLock, Stdout, StdoutLock};
+
+    macro_rules! handles {
+        ($($t:ty),*) => {
+            [$(
+                TypeId::of::<$t>(),
+                TypeId::of::<&'static $t>(),
+                TypeId::of::<&'static mut $t>(),
+                TypeId::of::<BufWriter<$t>>(),
+                TypeId::of::<&'static mut BufWriter<$t>>(),
+                TypeId::of::<LineWriter<$t>>(),
+                TypeId::of::<&'static mut LineWriter<$t>>(),
+            )*]
+        };
+    }
+
+    let id = type_id::<W>();
+    handles!(Stdout, Stderr, StdoutLock<'static>, StderrLock<'static>).contains(&id)
+}
+
+/// Returns the `TypeId` of `T` with all lifetimes replaced by `'static`,
+/// which is the same for every lifetime.
+#[cfg(feature = "std")]
+fn type_id<T: ?Sized>() -> core::any::TypeId {
+    use core::any::TypeId;
+    use core::marker::PhantomData;
+
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<T: ?Sized> NonStaticAny for PhantomData<T> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+
+    let phantom = PhantomData::<T>;
+    let erased: &dyn NonStaticAny = &phantom;
+    // SAFETY: Only the lifetime bound of the trait object changes. The method
+    // reads no data and lifetimes do not exist at run time, so it returns the
+    // `TypeId` that `T` has with every lifetime being `'static`.
+    let erased: &(dyn NonStaticAny + 'static) = unsafe { core::mem::transmute(erased) };
+    erased.type_id()
+}
+
+/// Adapter that turns a `core::fmt::Write` into a [`Sink`].
+///
+/// With the `std` feature, formatting errors are reported as `io::Error`s.
+/// Flushing does nothing.
+#[derive(Clone)]
+pub struct FmtSink<W>(pub W);
+
+impl<W: fmt::Write> Sink for FmtSink<W> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        self.0.write_str(s).map_err(|_| fmt_error())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn fmt_error() -> io::Error {
+    io::Error::other(crate::error::FormatFailed)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn fmt_error() -> fmt::Error {
+    fmt::Error
+}
+
+/// The default destination without the `std` feature, which rejects all output.
+#[cfg(not(feature = "std"))]
+pub(crate) struct Unset;
+
+#[cfg(not(feature = "std"))]
+impl Sink for Unset {
+    fn write_str(&mut self, _s: &str) -> Result<()> {
+        Err(fmt::Error)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Marker selecting the `std::io::Write` implementation of [`IntoSink`].
+#[cfg(feature = "std")]
+pub enum IoMarker {}
+
+/// Marker selecting the `core::fmt::Write` implementation of [`IntoSink`].
+pub enum FmtMarker {}
+
+/// Marker selecting the identity implementation of [`IntoSink`] for sinks.
+pub enum SinkMarker {}
+
+/// Conversion into a [`Sink`].
+///
+/// The marker type `M` only exists to tell the `std::io::Write`,
+/// `core::fmt::Write` and [`Sink`] implementations apart and is always inferred.
+pub trait IntoSink<'a, M> {
+    /// The sink produced by the conversion.
+    type Sink: Sink + 'a;
+
+    /// Wraps `self` into a sink.
+    fn into_sink(self) -> Self::Sink;
+}
+
+#[cfg(feature = "std")]
+impl<'a, W: io::Write + 'a> IntoSink<'a, IoMarker> for W {
+    type Sink = IoSink<W>;
+
+    fn into_sink(self) -> Self::Sink {
+        IoSink(self)
+    }
+}
+
+impl<'a, W: fmt::Write + 'a> IntoSink<'a, FmtMarker> for W {
+    type Sink = FmtSink<W>;
+
+    fn into_sink(self) -> Self::Sink {
+        FmtSink(self)
+    }
+}
+
+impl<'a, S: Sink + 'a> IntoSink<'a, SinkMarker> for S {
+    type Sink = S;
+
+    fn into_sink(self) -> Self::Sink {
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_shared_streams() {
+    use alloc::vec::Vec;
+
+    assert!(IoSink(io::stdout()).is_shared());
+    assert!(IoSink(io::stderr()).is_shared());
+    assert!(IoSink(&io::stdout()).is_shared());
+    assert!(IoSink(&mut io::stderr()).is_shared());
+    assert!(!IoSink(Vec::new()).is_shared());
+    assert!(!IoSink(&mut Vec::new()).is_shared());
+    assert!(!IoSink(io::sink()).is_shared());
+    assert!(SharedIoSink(Vec::new()).is_shared());
+
+    // Locks and buffering wrappers are std streams; other writers are not
+    assert!(IoSink(io::stdout().lock()).is_std_stream());
+    assert!(IoSink(&mut io::stderr().lock()).is_std_stream());
+    assert!(IoSink(io::BufWriter::new(io::stdout())).is_std_stream());
+    assert!(IoSink(io::LineWriter::new(io::stderr().lock())).is_std_stream());
+    assert!(SharedIoSink(&io::stderr()).is_std_stream());
+    assert!(!IoSink(io::BufWriter::new(Vec::new())).is_std_stream());
+    assert!(!SharedIoSink(Vec::new()).is_std_stream());
+    assert!(!IoSink(&mut &mut Vec::<u8>::new()).is_std_stream());
+}
+
+#[test]
+fn test_assembly_chunks() {
+    use alloc::vec::Vec;
+
+    /// A shared destination that records each piece it is handed.
+    struct Pieces(Vec<String>);
+
+    impl Sink for Pieces {
+        fn write_str(&mut self, s: &str) -> Result<()> {
+            self.0.push(String::from(s));
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_shared(&self) -> bool {
+            true
+        }
+    }
+
+    let mut pieces = Pieces(Vec::new());
+    let short = "x".repeat(10);
+    crate::pprint!(file=&mut pieces, short, short).unwrap();
+    assert_eq!(pieces.0, [alloc::format!("{short} {short}\n")]);
+
+    // Longer prints are split between writes, into pieces of at most 8 KiB
+    let (long, longer) = ("y".repeat(5000), "z".repeat(10_000));
+    pieces.0.clear();
+    crate::pprint!(file=&mut pieces, long, long, longer, long).unwrap();
+    let lengths: Vec<_> = pieces.0.iter().map(String::len).collect();
+    assert_eq!(lengths, [5001, 5001, 10_000, 5002]);
+    assert_eq!(pieces.0.concat(), alloc::format!("{long} {long} {longer} {long}\n"));
+}
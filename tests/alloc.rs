@@ -0,0 +1,80 @@
+#![cfg(feature = "std")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use pyprint::{dprint, pprint};
+
+struct CountingAlloc;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn note_allocation() {
+    if COUNTING.try_with(Cell::get).unwrap_or(false) {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        note_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        note_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Counts the allocations made by `f` on the current thread.
+fn count_allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|n| n.set(0));
+    COUNTING.with(|c| c.set(true));
+    f();
+    COUNTING.with(|c| c.set(false));
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn stdout_printing_does_not_allocate() {
+    pprint!("warm up").unwrap();
+    let allocations = count_allocations(|| {
+        for i in 0..10 {
+            pprint!("value:", i, 1.5, sep=" ", end="\n").unwrap();
+            dprint!("debug", Some(i), flush=true).unwrap();
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn private_writers_are_not_assembled() {
+    let mut buf = Vec::with_capacity(4096);
+    let allocations = count_allocations(|| {
+        for i in 0..10 {
+            pprint!(file=&mut buf, "value:", i, 1.5, sep=" ").unwrap();
+        }
+    });
+    // Only the boxed destination of each printer
+    assert_eq!(allocations, 10);
+}
+
+#[test]
+fn spread_printing_streams_items() {
+    let print_items = |n: u32| count_allocations(|| {
+        pprint!(*0..n, limit=n as usize / 2, file=std::io::sink()).unwrap();
+    });
+    // Output is assembled in a bounded buffer, so memory does not grow with the items
+    assert_eq!(print_items(10_000), print_items(1_000_000));
+}
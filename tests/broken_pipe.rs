@@ -0,0 +1,93 @@
+//! Checks the broken pipe policies by printing into a pipe whose reader is gone.
+//!
+//! The test re-runs its own binary as a child process with stdout piped. The
+//! child reports on stderr when it is about to print, the parent then closes
+//! its end of the pipe, and the child prints until the pipe breaks.
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::Stdio;
+
+use pyprint::error::{set_broken_pipe_policy, BrokenPipePolicy};
+use pyprint::{eprn, pprn, PrintConfig};
+
+const LINES: usize = 100_000;
+
+fn print_into_closed_pipe(mode: &str) -> ! {
+    set_broken_pipe_policy(match mode {
+        "ignore" => BrokenPipePolicy::Ignore,
+        "per-call" | "config" | "propagate" => BrokenPipePolicy::Propagate,
+        _ => BrokenPipePolicy::Exit,
+    });
+    let mut config = PrintConfig::new().file(std::io::stdout());
+    if mode == "config" {
+        config = config.broken_pipe(BrokenPipePolicy::Ignore);
+    }
+    eprn!("ready");
+    // Waits for the parent to close the pipe
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).unwrap();
+    for i in 0..LINES {
+        match mode {
+            "per-call" => pprn!("line", i, broken_pipe=BrokenPipePolicy::Ignore),
+            "file" => pprn!("line", i, file=std::io::stdout()),
+            "config" | "exit-config" => pprn!(@config, "line", i),
+            "stderr" => eprn!("line", i),
+            _ => pprn!("line", i),
+        }
+    }
+    eprn!("done");
+    std::process::exit(3)
+}
+
+/// Runs the child in `mode` and returns its exit code and stderr.
+fn run_child(mode: &str) -> (Option<i32>, String) {
+    let mut child = common::child("broken_pipe_policies", mode)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    while !line.starts_with("ready") {
+        line.clear();
+        assert_ne!(stderr.read_line(&mut line).unwrap(), 0, "child exited early");
+    }
+    drop(child.stdout.take());
+    let mut rest = String::new();
+    if mode == "stderr" {
+        drop(stderr);
+        drop(child.stdin.take());
+    } else {
+        drop(child.stdin.take());
+        stderr.read_to_string(&mut rest).unwrap();
+    }
+    (child.wait().unwrap().code(), rest)
+}
+
+#[test]
+fn broken_pipe_policies() {
+    if let Some(mode) = common::child_mode() {
+        print_into_closed_pipe(&mode);
+    }
+    // The global policy applies to stdout and stderr however they are named
+    for mode in ["exit", "file", "exit-config", "stderr"] {
+        let (code, stderr) = run_child(mode);
+        assert_eq!(code, Some(0), "{mode}: stderr: {stderr}");
+        assert!(!stderr.contains("done") && !stderr.contains("panicked"), "{mode}: stderr: {stderr}");
+    }
+
+    for mode in ["ignore", "per-call", "config"] {
+        let (code, stderr) = run_child(mode);
+        assert_eq!(code, Some(3), "{mode}: stderr: {stderr}");
+        assert!(stderr.contains("done") && !stderr.contains("panicked"), "{mode}: stderr: {stderr}");
+    }
+
+    let (code, stderr) = run_child("propagate");
+    assert_ne!(code, Some(0));
+    assert!(stderr.contains("pprn! at tests/broken_pipe.rs"), "stderr: {stderr}");
+    assert!(stderr.contains("output closed"), "stderr: {stderr}");
+}
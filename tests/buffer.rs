@@ -0,0 +1,49 @@
+//! Checks that buffered stdout is written out in full at process exit.
+//!
+//! The test re-runs its own binary as a child process, which buffers stdout,
+//! prints from several threads and exits through `std::process::exit`.
+#![cfg(feature = "std")]
+
+mod common;
+
+use pyprint::buffer::{self, FlushPolicy};
+use pyprint::pprn;
+
+const THREADS: usize = 4;
+const LINES: usize = 2000;
+
+fn print_buffered_and_exit() -> ! {
+    // Ends the line the test harness started, so ours start on fresh lines.
+    pprn!();
+    buffer::buffer_stdout(FlushPolicy::Never);
+    let threads: Vec<_> = (0..THREADS)
+        .map(|t| {
+            std::thread::spawn(move || {
+                for i in 0..LINES {
+                    pprn!("out", t, i, "alpha", "beta", sep=",");
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    std::process::exit(0)
+}
+
+#[test]
+fn buffered_stdout_is_flushed_at_exit() {
+    if common::child_mode().is_some() {
+        print_buffered_and_exit();
+    }
+    let output = common::child("buffered_stdout_is_flushed_at_exit", "exit").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let ours: Vec<_> = stdout.lines().filter(|l| l.contains("alpha")).collect();
+    assert_eq!(ours.len(), THREADS * LINES);
+    for line in ours {
+        let fields: Vec<_> = line.split(',').collect();
+        assert_eq!(fields.len(), 5, "interleaved line: {line:?}");
+        assert_eq!((fields[0], fields[3], fields[4]), ("out", "alpha", "beta"), "interleaved line: {line:?}");
+    }
+}
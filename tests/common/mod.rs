@@ -0,0 +1,27 @@
+//! Support for tests that re-run their own binary as a child process, so that
+//! the child's stdout and stderr can be captured through pipes or closed.
+//!
+//! A test calls [`child_mode`] first: in the child it gets the mode it was
+//! started with and does the printing, and in the parent it starts the child
+//! with [`child`] and checks what comes out.
+
+use std::env;
+use std::process::Command;
+
+/// Set in the environment of the child, to the mode it was started with.
+const CHILD_ENV: &str = "PYPRINT_TEST_CHILD";
+
+/// Returns the mode given to [`child`] in the child, or `None` in the parent.
+pub fn child_mode() -> Option<String> {
+    env::var(CHILD_ENV).ok()
+}
+
+/// Returns a command that runs only the test named `test` of the current
+/// binary as a child in `mode`, without capturing its output.
+pub fn child(test: &str, mode: &str) -> Command {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command
+        .args(["--exact", test, "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, mode);
+    command
+}
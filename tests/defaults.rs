@@ -0,0 +1,30 @@
+//! Checks that `write_py!` ignores the defaults of the print macros.
+//!
+//! `set_defaults` affects every thread, so this runs in its own test binary.
+#![cfg(feature = "std")]
+
+use std::fmt;
+
+use pyprint::{pprn, set_defaults, sprint, with_defaults, write_py, PrintConfig};
+
+struct Point(i32, i32);
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_py!(f, self.0, self.1, sep=", ")?;
+        write_py!(f, "", self.0 + self.1)
+    }
+}
+
+#[test]
+fn write_py_ignores_defaults() {
+    let mut out = String::new();
+    set_defaults(PrintConfig::new().sep("|").end(";\n").limit(1));
+    with_defaults(PrintConfig::new().sep("/").end("!"), || {
+        pprn!(Point(1, 2), "b", file=&mut out);
+    });
+    pprn!(Point(3, 4), "b", file=&mut out);
+    set_defaults(PrintConfig::new());
+    assert_eq!(out, "1, 2 3/b!3, 4 7|b;\n");
+    assert_eq!(sprint!(Point(5, 6)), "5, 6 11");
+}
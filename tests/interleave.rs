@@ -0,0 +1,62 @@
+//! Checks that prints from many threads to stdout and stderr never interleave.
+//!
+//! The test re-runs its own binary as a child process, so that the child's
+//! stdout and stderr can be captured through pipes.
+#![cfg(feature = "std")]
+
+mod common;
+
+use pyprint::{eprn, pprn};
+
+const THREADS: usize = 8;
+const LINES: usize = 500;
+
+fn print_from_threads() {
+    // Ends the line the test harness started, so ours start on fresh lines.
+    pprn!();
+    eprn!();
+    let threads: Vec<_> = (0..THREADS)
+        .map(|t| {
+            std::thread::spawn(move || {
+                for i in 0..LINES {
+                    pprn!("out", t, i, "alpha", "beta", "gamma", "delta", sep=",");
+                    eprn!("err", t, i, "alpha", "beta", "gamma", "delta", sep=",");
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+}
+
+fn check_lines(text: &str, prefix: &str) {
+    assert_eq!(text.lines().count(), THREADS * LINES);
+    for line in text.lines() {
+        let fields: Vec<_> = line.split(',').collect();
+        assert_eq!(fields.len(), 7, "interleaved line: {line:?}");
+        assert_eq!(fields[0], prefix, "interleaved line: {line:?}");
+        assert_eq!(&fields[3..], ["alpha", "beta", "gamma", "delta"], "interleaved line: {line:?}");
+    }
+}
+
+#[test]
+fn concurrent_lines_stay_intact() {
+    if common::child_mode().is_some() {
+        print_from_threads();
+        return;
+    }
+    let output = common::child("concurrent_lines_stay_intact", "threads").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    // The test harness adds its own summary lines around ours.
+    let ours = |text: &str, prefix: &str| {
+        text.lines()
+            .filter(|l| l.starts_with(prefix) || l.contains("alpha"))
+            .map(|l| format!("{l}\n"))
+            .collect::<String>()
+    };
+    check_lines(&ours(&stdout, "out,"), "out");
+    check_lines(&ours(&stderr, "err,"), "err");
+}